@@ -1,11 +1,11 @@
-use futures::FutureExt;
-use tokio::process::Command;
-
-use alloy::providers::{Provider, ProviderBuilder};
 use eyre::Result;
-use mevlog_backend::config::{middleware, schedule::get_schedule};
-use mevlog_backend::misc::utils::{measure_end, measure_start, uptime_ping};
-use tracing::{debug, error, info};
+use mevlog_backend::config::{app_config::init_app_config, middleware, schedule::get_schedule};
+use mevlog_backend::misc::{
+    cache_warmer::supervise,
+    shutdown::{begin_shutdown, shutdown_signal},
+};
+use tokio::task::JoinSet;
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -19,84 +19,30 @@ async fn main() -> Result<()> {
 }
 
 async fn run() -> Result<()> {
-    middleware::init_logs("scheduler.log");
-    tokio::spawn(async move {
-        let result = std::panic::AssertUnwindSafe(populate_mainnet_cache())
-            .catch_unwind()
-            .await;
+    let config = init_app_config()?;
+    middleware::init_logs(&config.log, "scheduler.log")?;
 
-        match result {
-            Ok(Ok(_)) => panic!("Cache task finished cleanly (which it never should)"),
-            Ok(Err(e)) => error!("Cache task errored: {:?}", e),
-            Err(e) => error!("Cache task panicked: {:?}", e),
-        }
-    });
+    let mut warmers = JoinSet::new();
+    for warmer in config.cache_warmers.clone() {
+        info!("Warming cache for chain {}", warmer.chain_id);
+        warmers.spawn(supervise(warmer));
+    }
 
-    let sched = get_schedule().await?;
+    let grace = config.shutdown.grace();
+    let mut sched = get_schedule(config).await?;
     sched.start().await?;
 
-    tokio::signal::ctrl_c().await?;
+    shutdown_signal().await;
+    begin_shutdown();
+    sched.shutdown().await?;
+    if tokio::time::timeout(grace, warmers.join_all())
+        .await
+        .is_err()
+    {
+        tracing::warn!("Cache warmers still running after {:?}", grace);
+    }
 
     info!("Scheduler ending");
 
     Ok(())
 }
-
-async fn populate_mainnet_cache() -> Result<()> {
-    let rpc_url = std::env::var("REMOTE_ETH_RPC_URL").expect("Missing REMOTE_ETH_RPC_URL env var");
-    let provider = ProviderBuilder::new().connect_http(rpc_url.parse()?);
-    tracing::info!("Scheduler connected to HTTP provider");
-
-    let mut current_block_number = provider.get_block_number().await?;
-    loop {
-        let new_block_number = match provider.get_block_number().await {
-            Ok(block_number) => block_number,
-            Err(e) => {
-                error!("Failed to get block number: {}", &e);
-                continue;
-            }
-        };
-
-        if new_block_number == current_block_number {
-            tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
-            debug!("No new blocks, sleeping: {}", current_block_number);
-            continue;
-        }
-
-        current_block_number = new_block_number;
-
-        let start = measure_start("mevlog latest");
-        let _resp = match Command::new("mevlog")
-            .arg("search")
-            .arg("-b")
-            .arg("latest")
-            .arg("--rpc-url")
-            .arg(rpc_url.clone())
-            .output()
-            .await
-        {
-            Ok(resp) => resp,
-            Err(e) => {
-                error!("Failed to run mevlog search latest: {}", &e);
-                continue;
-            }
-        };
-        measure_end(start);
-
-        if new_block_number % 10 == 0 {
-            let uptime_url = std::env::var("UPTIME_URL_MAINNET_CACHE")
-                .expect("Missing UPTIME_URL_MAINNET_CACHE env var");
-            info!("Mainnet cache uptime ping");
-
-            match uptime_ping(&uptime_url).await {
-                Ok(_) => {}
-                Err(e) => {
-                    error!("Failed to uptime ping: {}", &e);
-                }
-            }
-        }
-    }
-
-    #[allow(unreachable_code)]
-    Ok(())
-}
@@ -1,7 +1,19 @@
 use axum::http::StatusCode;
-use axum::middleware::from_fn;
+use axum::middleware::{from_fn, from_fn_with_state};
 use eyre::Result;
-use mevlog_backend::config::{cors, middleware, routes::app};
+use mevlog_backend::config::{
+    app_config::init_app_config,
+    cors,
+    middleware::{self, RateLimiter},
+    routes::{admin_app, app},
+};
+use mevlog_backend::misc::{
+    mevlog_cmd::{running_processes, wait_for_runs},
+    shutdown::{begin_shutdown, shutdown_signal, shutdown_started},
+};
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::{
@@ -9,41 +21,40 @@ use tower_http::{
 };
 use tracing::info;
 
+/// Time given to cancelled mevlog runs to be killed and reaped.
+const MEVLOG_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main(flavor = "current_thread")]
 #[hotpath::main]
 async fn main() -> Result<()> {
     let run_handle = tokio::spawn(async { run().await });
-
-    tokio::select! {
-        res = run_handle => {
-            res??;
-        }
-
-        _ = tokio::signal::ctrl_c() => {
-            tracing::info!("Ctrl-C received, shutting down…");
-        }
-    }
-
-    Ok(())
+    run_handle.await?
 }
 
 async fn run() -> Result<()> {
-    middleware::init_logs("server.log");
+    let config = init_app_config()?;
+    middleware::init_logs(&config.log, "server.log")?;
 
-    let app = hotpath::future!(app(), log = true)
+    let app = hotpath::future!(app(config.clone()), log = true)
         .await
+        .layer(from_fn_with_state(
+            Arc::new(RateLimiter::from_config(&config.rate_limit)),
+            middleware::rate_limit,
+        ))
+        .layer(from_fn(middleware::reject_when_draining))
         .layer(from_fn(middleware::request_tracing))
         .layer(from_fn(middleware::only_ssl))
         .layer(TimeoutLayer::with_status_code(
             StatusCode::REQUEST_TIMEOUT,
-            Duration::from_secs(10),
+            config.request_timeout(),
         ))
+        .layer(from_fn(middleware::record_metrics))
         .layer(CompressionLayer::new())
         .layer(CatchPanicLayer::new())
         .layer(from_fn(middleware::security_headers))
         .layer(cors());
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
+    let port = config.port;
 
     if TcpListener::bind(format!("0.0.0.0:{port}")).await.is_err() {
         eyre::bail!("Port {} is already in use", port);
@@ -51,9 +62,56 @@ async fn run() -> Result<()> {
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}")).await?;
 
+    if config.metrics.admin_port != 0 {
+        let admin_addr = format!(
+            "{}:{}",
+            config.metrics.admin_host, config.metrics.admin_port
+        );
+        let admin_listener = TcpListener::bind(&admin_addr).await?;
+        info!("Serving metrics on {}", admin_addr);
+        tokio::spawn(async move {
+            let admin =
+                axum::serve(admin_listener, admin_app()).with_graceful_shutdown(shutdown_started());
+            if let Err(e) = admin.await {
+                tracing::error!("Admin server failed: {}", &e);
+            }
+        });
+    }
+
     println!("Server started at http://localhost:{}", port);
     info!("Listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    let drain = config.shutdown.drain();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        info!("Draining for {:?}, new requests get 503", drain);
+        begin_shutdown();
+        tokio::time::sleep(drain).await;
+        info!("Closing the listener");
+    });
+
+    // Connections still open past the grace period are dropped
+    let grace = config.shutdown.grace();
+    tokio::select! {
+        res = server.into_future() => res?,
+        _ = async {
+            shutdown_started().await;
+            tokio::time::sleep(drain + grace).await;
+        } => tracing::warn!("Connections still open after {:?}, closing them", grace),
+    }
+
+    // Runs still going when the runtime shuts down drop their child,
+    // which `kill_on_drop` kills
+    if !wait_for_runs(MEVLOG_EXIT_TIMEOUT).await {
+        tracing::warn!(
+            "{} mevlog runs still in progress, their processes are killed on exit",
+            running_processes().len()
+        );
+    }
+    info!("Server stopped");
 
     Ok(())
 }
@@ -1,4 +1,4 @@
-import React, { useState, useEffect } from 'react';
+import React, { useState, useEffect, useRef } from 'react';
 import ChainSelector from './ChainSelector';
 import CommandBuilder from './CommandBuilder';
 
@@ -44,7 +44,7 @@ const SearchForm = ({ initialValues = {}, onSubmit }) => {
       if (cmdOutput) {
         cmdOutput.innerHTML = "<div class='spinner-container'><div class='spinner'></div><div>Loading...</div></div>";
       }
-      startWebSocketConnection(urlParams);
+      startSearch(urlParams);
     }
   }, []);
 
@@ -103,78 +103,190 @@ const SearchForm = ({ initialValues = {}, onSubmit }) => {
     return window.location.protocol === 'https:' ? 'wss:' : 'ws:';
   };
 
+  const PING_INTERVAL_MS = 25000;
 
-  const startWebSocketConnection = (params) => {
-    const socket = new WebSocket(`${wsProtocol()}//${window.location.host}/ws/search?${params.toString()}`);
+  // One socket is kept open for every search on the page, see ClientMessage
+  // and ServerMessage in websocket/search_controller.rs for the protocol
+  const socketRef = useRef(null);
+  const pingTimerRef = useRef(null);
+  const searchIdRef = useRef(0);
+  const activeSearchIdRef = useRef(null);
+  const [searchRunning, setSearchRunning] = useState(false);
 
-    // Show progress indicator
+  useEffect(() => {
+    return () => {
+      clearInterval(pingTimerRef.current);
+      if (socketRef.current) {
+        socketRef.current.close();
+      }
+    };
+  }, []);
+
+  const hideProgress = () => {
     const progressDiv = document.getElementById('search-progress');
     if (progressDiv) {
-      progressDiv.style.display = 'block';
+      progressDiv.style.display = 'none';
     }
+  };
 
-    socket.addEventListener('open', (event) => {
-      console.log('Connected to WebSocket server');
-
-      // Always clear React viewer when WebSocket opens to ensure fresh results
-      if (window.clearMevlogViewer) {
-        window.clearMevlogViewer();
-      }
-    });
+  const showProgress = (text) => {
+    const progressDiv = document.getElementById('search-progress');
+    if (progressDiv) {
+      progressDiv.style.display = 'block';
+    }
+    const progressText = document.getElementById('progress-text');
+    if (progressText) {
+      progressText.textContent = text;
+    }
+  };
 
+  const appendOutput = (text) => {
     const cmdOutput = document.querySelector('.js-cmd-output');
-    let isFirstMessage = true;
+    if (!cmdOutput) {
+      return;
+    }
+    if (cmdOutput.querySelector('.spinner-container') || cmdOutput.innerHTML.includes('Press search to query')) {
+      cmdOutput.innerHTML = '';
+    }
+    const line = document.createElement('div');
+    line.textContent = text;
+    cmdOutput.appendChild(line);
+    cmdOutput.style.display = 'block';
+  };
 
-    socket.addEventListener('message', (event) => {
-      console.log('Raw message received:', event.data);
-      try {
-        // Try to parse as JSON first
-        const jsonData = JSON.parse(event.data);
-        console.log('Parsed JSON data:', jsonData);
+  const finishSearch = () => {
+    activeSearchIdRef.current = null;
+    setSearchRunning(false);
+    hideProgress();
+  };
 
+  const handleServerMessage = (message) => {
+    // Envelopes of a cancelled or superseded search may still be in flight
+    if (message.search_id != null && message.search_id !== activeSearchIdRef.current) {
+      return;
+    }
+
+    switch (message.type) {
+      case 'result': {
         if (window.updateMevlogViewer) {
-          console.log('Updating React with JSON data:', jsonData);
-          // Send JSON data to React component
-          window.updateMevlogViewer(jsonData);
-          // Hide the text output when React takes over
-          cmdOutput.style.display = 'none';
-        } else {
-          console.warn('updateMevlogViewer not available, showing in output');
-          if (isFirstMessage) {
-            cmdOutput.innerHTML = '';
-            isFirstMessage = false;
+          window.updateMevlogViewer(message.data);
+          const cmdOutput = document.querySelector('.js-cmd-output');
+          if (cmdOutput) {
+            cmdOutput.style.display = 'none';
           }
-          cmdOutput.insertAdjacentHTML('beforeend', `<pre>${JSON.stringify(jsonData, null, 2)}</pre>`);
+        } else {
+          appendOutput(JSON.stringify(message.data, null, 2));
         }
-      } catch (e) {
-        console.error('Error parsing JSON, raw data:', event.data);
-        console.error('JSON parse error:', e);
-        // If it's not JSON, display as regular text
-        if (isFirstMessage) {
-          cmdOutput.innerHTML = '';
-          isFirstMessage = false;
+        break;
+      }
+      case 'progress': {
+        const block = message.last_block ? ` block ${message.last_block},` : '';
+        showProgress(`Processing blocks...${block} ${message.matches} matches`);
+        break;
+      }
+      case 'error': {
+        if (window.updateMevlogViewer) {
+          window.updateMevlogViewer({ error: message.error });
+        } else {
+          appendOutput(message.error);
         }
-        cmdOutput.insertAdjacentHTML('beforeend', `<div>${event.data}</div>`);
+        break;
       }
-    });
-
-    socket.addEventListener('close', (event) => {
-      console.log('Disconnected from WebSocket server');
+      case 'done': {
+        finishSearch();
+        break;
+      }
+      default:
+        break;
+    }
+  };
 
-      // Hide progress indicator when connection closes
-      if (progressDiv) {
-        progressDiv.style.display = 'none';
+  const openSocket = () => {
+    return new Promise((resolve, reject) => {
+      const current = socketRef.current;
+      if (current && current.readyState === WebSocket.OPEN) {
+        resolve(current);
+        return;
+      }
+      if (current && current.readyState === WebSocket.CONNECTING) {
+        current.addEventListener('open', () => resolve(current), { once: true });
+        current.addEventListener('error', reject, { once: true });
+        return;
       }
-    });
 
-    socket.addEventListener('error', (event) => {
-      console.error('WebSocket error:', event);
+      const socket = new WebSocket(`${wsProtocol()}//${window.location.host}/ws/search`);
+      socketRef.current = socket;
 
-      // Hide progress indicator on error
-      if (progressDiv) {
-        progressDiv.style.display = 'none';
-      }
+      socket.addEventListener('open', () => {
+        console.log('Connected to WebSocket server');
+        clearInterval(pingTimerRef.current);
+        pingTimerRef.current = setInterval(() => {
+          if (socket.readyState === WebSocket.OPEN) {
+            socket.send(JSON.stringify({ type: 'ping' }));
+          }
+        }, PING_INTERVAL_MS);
+        resolve(socket);
+      }, { once: true });
+
+      socket.addEventListener('message', (event) => {
+        try {
+          handleServerMessage(JSON.parse(event.data));
+        } catch (e) {
+          console.error('Invalid message from server:', event.data, e);
+        }
+      });
+
+      socket.addEventListener('close', () => {
+        console.log('Disconnected from WebSocket server');
+        clearInterval(pingTimerRef.current);
+        if (socketRef.current === socket) {
+          socketRef.current = null;
+        }
+        if (activeSearchIdRef.current != null) {
+          finishSearch();
+        }
+      });
+
+      socket.addEventListener('error', (event) => {
+        console.error('WebSocket error:', event);
+        reject(event);
+      }, { once: true });
+    });
+  };
+
+  const startSearch = async (params) => {
+    const searchParams = {};
+    params.forEach((value, key) => {
+      searchParams[key] = key === 'chain_id' ? parseInt(value) : value;
     });
+
+    // Always clear React viewer to ensure fresh results
+    if (window.clearMevlogViewer) {
+      window.clearMevlogViewer();
+    }
+
+    searchIdRef.current += 1;
+    const searchId = searchIdRef.current;
+    activeSearchIdRef.current = searchId;
+    setSearchRunning(true);
+    showProgress('Processing blocks...');
+
+    try {
+      const socket = await openSocket();
+      // Starting a new search stops the previous one on the server
+      socket.send(JSON.stringify({ type: 'search', id: searchId, params: searchParams }));
+    } catch (e) {
+      appendOutput('Failed to connect to the server, please try again.');
+      finishSearch();
+    }
+  };
+
+  const cancelSearch = () => {
+    const socket = socketRef.current;
+    if (socket && socket.readyState === WebSocket.OPEN) {
+      socket.send(JSON.stringify({ type: 'cancel' }));
+    }
+    finishSearch();
   };
 
   const handleSubmit = (e) => {
@@ -221,8 +333,8 @@ const SearchForm = ({ initialValues = {}, onSubmit }) => {
     // Update browser URL
     window.history.pushState({}, '', url);
 
-    // Establish WebSocket connection
-    startWebSocketConnection(params);
+    // Run the search over the shared WebSocket connection
+    startSearch(params);
   };
 
   const toggleFilters = () => {
@@ -729,6 +841,15 @@ const SearchForm = ({ initialValues = {}, onSubmit }) => {
             >
               Help
             </button>
+            {searchRunning && (
+              <button
+                type="button"
+                onClick={cancelSearch}
+                style={helpButtonStyle}
+              >
+                Cancel
+              </button>
+            )}
             <button
               type="submit"
               style={buttonStyle}
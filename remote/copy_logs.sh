@@ -1,3 +1,3 @@
-scp -r $TARGET_NODE:/root/mevlog-backend/scheduler.log varlogs/scheduler.log
-scp -r $TARGET_NODE:/root/mevlog-backend/server.log varlogs/server.log
+scp -r "$TARGET_NODE:/root/mevlog-backend/scheduler.log*" varlogs/
+scp -r "$TARGET_NODE:/root/mevlog-backend/server.log*" varlogs/
 scp -r $TARGET_NODE:/root/mevlog-backend/dbg.log varlogs/dbg.log
@@ -1 +1 @@
-ssh $TARGET_NODE 'tail -f /root/mevlog-backend/server.log /root/mevlog-backend/scheduler.log'
+ssh $TARGET_NODE 'cd /root/mevlog-backend; tail -F $(ls -t server.log* | head -1) $(ls -t scheduler.log* | head -1)'
@@ -1,4 +1,12 @@
 ssh $TARGET_NODE << EOF
+# SIGTERM lets both processes drain, see [shutdown] in the config
+pkill -TERM -x server
+pkill -TERM -x scheduler
+for i in \$(seq 1 40); do
+    pgrep -x server > /dev/null || pgrep -x scheduler > /dev/null || break
+    sleep 1
+done
+
 for session in \$(screen -ls | grep "mevlog-http" | awk '{print \$1}'); do
     echo "Terminating session \$session"
     screen -X -S "\$session" quit
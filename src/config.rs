@@ -1,3 +1,5 @@
+pub mod app_config;
+pub mod env_overrides;
 pub mod middleware;
 pub mod routes;
 pub mod schedule;
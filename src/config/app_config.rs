@@ -0,0 +1,735 @@
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use eyre::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::config::env_overrides::with_env_overrides;
+use crate::config::middleware::Env;
+use crate::misc::cache_warmer::WarmerConfig;
+
+static APP_CONFIG: OnceLock<Arc<AppConfig>> = OnceLock::new();
+
+/// The config [`init_app_config`] loaded, or loads it on first use, e.g.
+/// in tests. Panics if it's invalid.
+pub fn app_config() -> &'static AppConfig {
+    APP_CONFIG.get_or_init(|| match AppConfig::load() {
+        Ok(config) => Arc::new(config),
+        Err(e) => panic!("{e}"),
+    })
+}
+
+/// Loads the config once at startup, so both binaries fail right away
+/// listing everything that's wrong with it.
+pub fn init_app_config() -> Result<Arc<AppConfig>> {
+    let config = Arc::new(AppConfig::load()?);
+    let _ = APP_CONFIG.set(config);
+    Ok(APP_CONFIG.get().expect("config is set").clone())
+}
+
+/// Settings of the server and the scheduler. Read from a TOML file, then
+/// overridden by environment variables named after the setting's path,
+/// e.g. `PORT` or `SEARCH_BATCH_SIZE` for `[search] batch_size`, and
+/// parsed as the setting's type. Lists are given comma separated in
+/// environment variables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub env: Env,
+    /// Suffix of the bundled asset names, set on deploy.
+    pub deployed_at: String,
+    pub port: u16,
+    /// Requests running longer are answered with `408`.
+    pub request_timeout_secs: u64,
+    /// Prices read from this JSON file when no price API answers.
+    pub prices_file: Option<String>,
+    pub search: SearchConfig,
+    pub mevlog: MevlogConfig,
+    pub rpc: RpcConfig,
+    pub chains: ChainsConfig,
+    pub uptime_url: UptimeUrls,
+    pub rate_limit: RateLimitConfig,
+    pub explore_cache: ExploreCacheConfig,
+    pub block_watcher: BlockWatcherConfig,
+    pub rpc_probe: RpcProbeConfig,
+    pub price_history: PriceHistoryConfig,
+    pub custom_rpc: CustomRpcConfig,
+    pub metrics: MetricsConfig,
+    pub admin: AdminConfig,
+    pub scheduler: SchedulerConfig,
+    pub log: LogConfig,
+    pub shutdown: ShutdownConfig,
+    /// `[[cache_warmer]]` tables, overridden by the `CACHE_WARMER_*`
+    /// environment variables, see [`WarmerConfig::apply_env_overrides`].
+    #[serde(rename = "cache_warmer")]
+    pub cache_warmers: Vec<WarmerConfig>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            env: Env::Development,
+            deployed_at: String::new(),
+            port: 3000,
+            request_timeout_secs: 10,
+            prices_file: None,
+            search: SearchConfig::default(),
+            mevlog: MevlogConfig::default(),
+            rpc: RpcConfig::default(),
+            chains: ChainsConfig::default(),
+            uptime_url: UptimeUrls::default(),
+            rate_limit: RateLimitConfig::default(),
+            explore_cache: ExploreCacheConfig::default(),
+            block_watcher: BlockWatcherConfig::default(),
+            rpc_probe: RpcProbeConfig::default(),
+            price_history: PriceHistoryConfig::default(),
+            custom_rpc: CustomRpcConfig::default(),
+            metrics: MetricsConfig::default(),
+            admin: AdminConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            log: LogConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            cache_warmers: vec![WarmerConfig::new(1)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Blocks mevlog fetches at once while streaming.
+    pub batch_size: u64,
+    /// Widest block range a search may cover.
+    pub max_block_range: u64,
+    /// Streaming searches are stopped after this long.
+    pub timeout_secs: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 20,
+            max_block_range: 500,
+            timeout_secs: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MevlogConfig {
+    /// A single mevlog run, or the wait for its next line, times out
+    /// after this long.
+    pub timeout_secs: u64,
+    pub max_concurrent: usize,
+    pub max_per_chain: usize,
+    pub max_queued: usize,
+    pub max_wait_secs: u64,
+}
+
+impl Default for MevlogConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            max_concurrent: 16,
+            max_per_chain: 8,
+            max_queued: 64,
+            max_wait_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// How long a chain's RPC list from chain-info is reused.
+    pub url_cache_secs: u64,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self { url_cache_secs: 60 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainsConfig {
+    /// Chains `/api/chains` lists when no filter is given.
+    pub default_ids: Vec<u64>,
+}
+
+impl Default for ChainsConfig {
+    fn default() -> Self {
+        Self {
+            default_ids: vec![1, 137, 8453, 10, 130, 43114, 56, 42161],
+        }
+    }
+}
+
+/// Uptime monitors pinged by the scheduler's jobs, skipped when not set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UptimeUrls {
+    pub scheduler: Option<String>,
+    pub rpc_prober: Option<String>,
+}
+
+/// Requests per minute a client IP may make, `0` turns limiting off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub html_per_min: u32,
+    pub json_per_min: u32,
+    pub ws_per_min: u32,
+    /// Proxies whose `X-Real-IP` and `X-Forwarded-For` are believed, besides
+    /// loopback. Other peers are limited by their own address.
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            html_per_min: 120,
+            json_per_min: 60,
+            ws_per_min: 20,
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExploreCacheConfig {
+    pub capacity: usize,
+    /// Final blocks are also kept here when set.
+    pub dir: Option<String>,
+}
+
+impl Default for ExploreCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockWatcherConfig {
+    pub poll_ms: u64,
+}
+
+impl Default for BlockWatcherConfig {
+    fn default() -> Self {
+        Self { poll_ms: 2000 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcProbeConfig {
+    /// Where the scheduler writes probe results for the server to read.
+    pub file: String,
+    pub chains: Vec<u64>,
+}
+
+impl Default for RpcProbeConfig {
+    fn default() -> Self {
+        Self {
+            file: "rpc_probes.json".to_string(),
+            chains: vec![1, 10, 56, 137, 8453, 42161],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriceHistoryConfig {
+    /// Where the scheduler writes the price history for the server to read.
+    pub file: String,
+    /// Native currency symbols the history is kept of.
+    pub symbols: Vec<String>,
+    /// How far back a symbol without any history is backfilled.
+    pub backfill_days: u64,
+}
+
+impl Default for PriceHistoryConfig {
+    fn default() -> Self {
+        Self {
+            file: "price_history.json".to_string(),
+            symbols: vec!["ETH".to_string(), "BNB".to_string()],
+            backfill_days: 365,
+        }
+    }
+}
+
+/// Rules for RPC URLs users bring in, see
+/// [`crate::misc::custom_rpc::RpcPolicy`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomRpcConfig {
+    pub schemes: Vec<String>,
+    pub allowed_hosts: Vec<String>,
+    pub denied_hosts: Vec<String>,
+    /// On by default in production.
+    pub block_private_ips: Option<bool>,
+}
+
+impl Default for CustomRpcConfig {
+    fn default() -> Self {
+        Self {
+            schemes: vec!["https".to_string()],
+            allowed_hosts: vec![],
+            denied_hosts: vec![],
+            block_private_ips: None,
+        }
+    }
+}
+
+/// `/metrics` is served on the main port to requests bearing `token`, and
+/// without it on `admin_port`. Not served at all when neither is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub token: Option<String>,
+    /// `0` doesn't open an admin port.
+    pub admin_port: u16,
+    /// Address the admin port is bound to.
+    pub admin_host: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            admin_port: 0,
+            admin_host: "127.0.0.1".to_string(),
+        }
+    }
+}
+
+/// Credentials of the `/admin` pages, which aren't served without a
+/// password.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            username: "admin".to_string(),
+            password: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Where the scheduler records its jobs and cache warmers for the
+    /// server to read.
+    pub status_file: String,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            status_file: "scheduler_status.json".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, with the request span's fields.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// Where and how the binaries log. Only production logs to files, other
+/// environments log to stdout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub rotation: LogRotation,
+    /// Rotated files kept, `0` keeps all of them.
+    pub max_files: usize,
+    pub dir: String,
+    /// IANA time zone of the timestamps, like `Europe/Warsaw`. Files are
+    /// still rotated at UTC boundaries.
+    pub timezone: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            rotation: LogRotation::Daily,
+            max_files: 14,
+            dir: "./".to_string(),
+            timezone: "UTC".to_string(),
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn timezone(&self) -> Option<jiff::tz::TimeZone> {
+        jiff::tz::TimeZone::get(&self.timezone).ok()
+    }
+}
+
+/// On Ctrl-C or SIGTERM new requests get `503` for `drain_secs` while
+/// searches and sockets wrap up, then connections still open get up to
+/// `grace_secs` to finish.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    pub drain_secs: u64,
+    pub grace_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_secs: 5,
+            grace_secs: 20,
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn drain(&self) -> Duration {
+        Duration::from_secs(self.drain_secs)
+    }
+
+    pub fn grace(&self) -> Duration {
+        Duration::from_secs(self.grace_secs)
+    }
+}
+
+impl AppConfig {
+    /// Reads `CONFIG_FILE`, or `config.toml` if there is one, and the
+    /// environment.
+    pub fn load() -> Result<Self> {
+        let (path, required) = match std::env::var("CONFIG_FILE") {
+            Ok(path) => (path, true),
+            Err(_) => ("config.toml".to_string(), false),
+        };
+        let file = match std::fs::read_to_string(&path) {
+            Ok(contents) => Some((path, contents)),
+            Err(e) if required => bail!("Failed to read config file {}: {}", path, e),
+            Err(_) => None,
+        };
+
+        Self::load_from(
+            file.as_ref()
+                .map(|(path, contents)| (path.as_str(), contents.as_str())),
+            |name| std::env::var(name).ok(),
+        )
+    }
+
+    /// Builds the config from an optional `(path, contents)` TOML file and
+    /// environment `lookup`, reporting every problem found at once.
+    pub fn load_from(
+        file: Option<(&str, &str)>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let mut errors = vec![];
+
+        let base = match file {
+            Some((path, contents)) => basic_toml::from_str::<Self>(contents).unwrap_or_else(|e| {
+                errors.push(format!("{path}: {e}"));
+                Self::default()
+            }),
+            None => Self::default(),
+        };
+
+        let mut config = with_env_overrides(base, &lookup, &mut errors)?;
+        if let Err(e) = WarmerConfig::apply_env_overrides(&mut config.cache_warmers, &lookup) {
+            errors.push(e.to_string());
+        }
+
+        errors.extend(config.validate());
+        if !errors.is_empty() {
+            bail!("Invalid configuration:\n  - {}", errors.join("\n  - "));
+        }
+        Ok(config)
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        let mut check = |valid: bool, message: &str| {
+            if !valid {
+                errors.push(message.to_string());
+            }
+        };
+
+        check(!self.deployed_at.is_empty(), "DEPLOYED_AT must be set");
+        check(self.port != 0, "PORT must not be 0");
+        check(
+            self.request_timeout_secs > 0,
+            "REQUEST_TIMEOUT_SECS must be at least 1",
+        );
+        check(
+            self.search.batch_size > 0,
+            "SEARCH_BATCH_SIZE must be at least 1",
+        );
+        check(
+            (1..=10_000).contains(&self.search.max_block_range),
+            "SEARCH_MAX_BLOCK_RANGE must be between 1 and 10000",
+        );
+        check(
+            self.search.timeout_secs > 0,
+            "SEARCH_TIMEOUT_SECS must be at least 1",
+        );
+        check(
+            self.mevlog.timeout_secs > 0,
+            "MEVLOG_TIMEOUT_SECS must be at least 1",
+        );
+        check(
+            self.mevlog.max_concurrent > 0,
+            "MEVLOG_MAX_CONCURRENT must be at least 1",
+        );
+        check(
+            self.mevlog.max_per_chain > 0,
+            "MEVLOG_MAX_PER_CHAIN must be at least 1",
+        );
+        check(
+            !self.chains.default_ids.is_empty(),
+            "CHAINS_DEFAULT_IDS must list at least one chain",
+        );
+        check(
+            self.block_watcher.poll_ms >= 100,
+            "BLOCK_WATCHER_POLL_MS must be at least 100",
+        );
+        check(
+            !self.custom_rpc.schemes.is_empty(),
+            "CUSTOM_RPC_SCHEMES must list at least one scheme",
+        );
+
+        check(
+            self.metrics
+                .token
+                .as_ref()
+                .is_none_or(|token| token.len() >= 16),
+            "METRICS_TOKEN must be at least 16 characters",
+        );
+        check(
+            self.admin
+                .password
+                .as_ref()
+                .is_none_or(|password| password.len() >= 12),
+            "ADMIN_PASSWORD must be at least 12 characters",
+        );
+        check(
+            self.metrics.admin_port == 0 || self.metrics.admin_port != self.port,
+            "METRICS_ADMIN_PORT must differ from PORT",
+        );
+        if self.log.timezone().is_none() {
+            errors.push(format!(
+                "LOG_TIMEZONE must be an IANA time zone like Europe/Warsaw, got '{}'",
+                self.log.timezone
+            ));
+        }
+
+        for proxy in &self.rate_limit.trusted_proxies {
+            if proxy.parse::<IpAddr>().is_err() {
+                errors.push(format!(
+                    "RATE_LIMIT_TRUSTED_PROXIES must list IP addresses, got '{proxy}'"
+                ));
+            }
+        }
+
+        let urls = [
+            ("UPTIME_URL_SCHEDULER", &self.uptime_url.scheduler),
+            ("UPTIME_URL_RPC_PROBER", &self.uptime_url.rpc_prober),
+        ];
+        for (name, url) in urls {
+            if let Some(url) = url
+                && url::Url::parse(url).is_err()
+            {
+                errors.push(format!("{name} must be a valid URL, got '{url}'"));
+            }
+        }
+
+        errors
+    }
+
+    pub fn host(&self) -> String {
+        match self.env {
+            Env::Development | Env::Test => "http://localhost:3000",
+            Env::Production => "https://mevlog.rs",
+        }
+        .to_string()
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn environment_overrides_file() -> Result<()> {
+        let file = r#"
+            env = "production"
+            deployed_at = "123"
+            port = 4000
+
+            [search]
+            batch_size = 50
+
+            [chains]
+            default_ids = [1, 10]
+        "#;
+        let config = AppConfig::load_from(
+            Some(("config.toml", file)),
+            lookup(&[
+                ("PORT", "5000"),
+                ("CHAINS_DEFAULT_IDS", "56, 137"),
+                ("UPTIME_URL_SCHEDULER", "https://uptime.example.org/ping"),
+                ("CUSTOM_RPC_BLOCK_PRIVATE_IPS", "false"),
+                ("LOG_FORMAT", "json"),
+                ("LOG_TIMEZONE", "America/St_Johns"),
+            ]),
+        )?;
+
+        assert_eq!(config.env, Env::Production);
+        assert_eq!(config.port, 5000);
+        assert_eq!(config.search.batch_size, 50);
+        assert_eq!(config.search.max_block_range, 500);
+        assert_eq!(config.chains.default_ids, vec![56, 137]);
+        assert_eq!(
+            config.uptime_url.scheduler.as_deref(),
+            Some("https://uptime.example.org/ping")
+        );
+        assert_eq!(config.custom_rpc.block_private_ips, Some(false));
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(
+            config
+                .log
+                .timezone()
+                .and_then(|tz| tz.iana_name().map(str::to_string)),
+            Some("America/St_Johns".to_string())
+        );
+        assert_eq!(config.cache_warmers.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn parses_environment_as_the_settings_type() -> Result<()> {
+        let file = r#"
+            deployed_at = "1"
+
+            [rpc_probe]
+            chains = []
+
+            [[cache_warmer]]
+            chain_id = 10
+            poll_secs = 2
+
+            [[cache_warmer]]
+            chain_id = 56
+        "#;
+        let config = AppConfig::load_from(
+            Some(("config.toml", file)),
+            lookup(&[
+                ("METRICS_TOKEN", "1234567890123456"),
+                ("PRICES_FILE", "true"),
+                ("EXPLORE_CACHE_DIR", "false"),
+                ("RPC_PROBE_CHAINS", "1, 10"),
+                ("CACHE_WARMER_56_POLL_SECS", "8"),
+            ]),
+        )?;
+
+        assert_eq!(config.metrics.token.as_deref(), Some("1234567890123456"));
+        assert_eq!(config.prices_file.as_deref(), Some("true"));
+        assert_eq!(config.explore_cache.dir.as_deref(), Some("false"));
+        assert_eq!(config.rpc_probe.chains, vec![1, 10]);
+        assert_eq!(
+            config.cache_warmers,
+            vec![
+                WarmerConfig {
+                    poll_secs: 2,
+                    ..WarmerConfig::new(10)
+                },
+                WarmerConfig {
+                    poll_secs: 8,
+                    ..WarmerConfig::new(56)
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reports_every_error_at_once() {
+        let file = "port = 3000\nunknown_setting = true\n";
+        let error = AppConfig::load_from(
+            Some(("config.toml", file)),
+            lookup(&[
+                ("ENV", "staging"),
+                ("SEARCH_BATCH_SIZE", "many"),
+                ("SEARCH_MAX_BLOCK_RANGE", "0"),
+                ("PORT", "70000"),
+                ("CACHE_WARMER_CHAINS", "bsc"),
+            ]),
+        )
+        .unwrap_err()
+        .to_string();
+
+        for expected in [
+            "config.toml: unknown field `unknown_setting`",
+            "SEARCH_BATCH_SIZE has an invalid value 'many'",
+            "PORT has an invalid value '70000'",
+            "SEARCH_MAX_BLOCK_RANGE must be between 1 and 10000",
+            "unknown variant `staging`",
+            "Invalid chain id 'bsc'",
+            "DEPLOYED_AT must be set",
+        ] {
+            assert!(error.contains(expected), "{expected} missing in: {error}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_timezone() {
+        for timezone in ["CEST+", "Europe/Nowhere", "+02:00x"] {
+            let error = AppConfig::load_from(
+                None,
+                lookup(&[("DEPLOYED_AT", "1"), ("LOG_TIMEZONE", timezone)]),
+            )
+            .unwrap_err()
+            .to_string();
+            assert!(error.contains("LOG_TIMEZONE must be an IANA time zone"));
+        }
+    }
+}
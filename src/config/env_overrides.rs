@@ -0,0 +1,284 @@
+use std::fmt;
+
+use eyre::Result;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::{Serialize, forward_to_deserialize_any};
+use serde_json::Value;
+
+/// Rebuilds `base` with the settings that have an environment variable set,
+/// each parsed as the type of its field. Invalid variables are added to
+/// `errors` and leave their setting as it was.
+pub fn with_env_overrides<T: Serialize + DeserializeOwned>(
+    base: T,
+    lookup: &impl Fn(&str) -> Option<String>,
+    errors: &mut Vec<String>,
+) -> Result<T> {
+    let original = serde_json::to_value(&base)?;
+    let mut value = original.clone();
+    let mut overrides = vec![];
+    collect_overrides(&mut value, "", "", lookup, &mut overrides);
+
+    loop {
+        let error = match T::deserialize(EnvDeserializer::new(value.clone(), String::new())) {
+            Ok(config) => return Ok(config),
+            Err(error) => error,
+        };
+        errors.push(error.to_string());
+
+        // Put back the setting that failed and try again with the others
+        let Some(index) = error
+            .name
+            .and_then(|name| overrides.iter().position(|(set, _)| *set == name))
+        else {
+            return Ok(base);
+        };
+        let (_, pointer) = overrides.swap_remove(index);
+        if let (Some(field), Some(original)) =
+            (value.pointer_mut(&pointer), original.pointer(&pointer))
+        {
+            *field = original.clone();
+        }
+    }
+}
+
+/// Replaces settings that have an environment variable set with its raw
+/// value, recording the variable name and JSON pointer of each.
+fn collect_overrides(
+    value: &mut Value,
+    prefix: &str,
+    pointer: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+    overrides: &mut Vec<(String, String)>,
+) {
+    let Value::Object(fields) = value else {
+        return;
+    };
+
+    for (key, field) in fields.iter_mut() {
+        let name = env_name(prefix, key);
+        let pointer = format!("{pointer}/{key}");
+        if field.is_object() {
+            collect_overrides(field, &name, &pointer, lookup, overrides);
+            continue;
+        }
+        if let Some(raw) = lookup(&name) {
+            *field = Value::from(raw.trim());
+            overrides.push((name, pointer));
+        }
+    }
+}
+
+fn env_name(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_uppercase()
+    } else {
+        format!("{prefix}_{}", key.to_uppercase())
+    }
+}
+
+#[derive(Debug)]
+struct EnvError {
+    /// Variable of the setting that failed to deserialize.
+    name: Option<String>,
+    message: String,
+}
+
+impl EnvError {
+    fn invalid(name: &str, raw: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            message: format!("{name} has an invalid value '{raw}'"),
+        }
+    }
+
+    fn named(mut self, name: &str) -> Self {
+        if self.name.is_none() && !name.is_empty() {
+            self.message = format!("{name}: {}", self.message);
+            self.name = Some(name.to_string());
+        }
+        self
+    }
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+impl de::Error for EnvError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self {
+            name: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for EnvError {
+    fn from(error: serde_json::Error) -> Self {
+        de::Error::custom(error)
+    }
+}
+
+/// Reads a setting from the config tree. Strings, which environment
+/// variables always are, are parsed into whatever the field asks for, and
+/// split on commas for lists.
+struct EnvDeserializer {
+    value: Value,
+    name: String,
+}
+
+impl EnvDeserializer {
+    fn new(value: Value, name: String) -> Self {
+        Self { value, name }
+    }
+}
+
+macro_rules! parse_strings {
+    ($($method:ident => $visit:ident),* $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvError> {
+            match self.value {
+                Value::String(raw) => match raw.parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => Err(EnvError::invalid(&self.name, &raw)),
+                },
+                value => value.$method(visitor).map_err(EnvError::from),
+            }
+            .map_err(|e| e.named(&self.name))
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for EnvDeserializer {
+    type Error = EnvError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvError> {
+        match self.value {
+            Value::Object(fields) => visitor.visit_map(Fields {
+                fields: fields.into_iter(),
+                value: None,
+                prefix: self.name.clone(),
+            }),
+            Value::Array(items) => visitor.visit_seq(Items {
+                items: items.into_iter(),
+                name: self.name.clone(),
+            }),
+            value => value.deserialize_any(visitor).map_err(EnvError::from),
+        }
+        .map_err(|e| e.named(&self.name))
+    }
+
+    parse_strings! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvError> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvError> {
+        let items = match self.value {
+            Value::String(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(Value::from)
+                .collect(),
+            Value::Array(items) => items,
+            value => {
+                return value
+                    .deserialize_seq(visitor)
+                    .map_err(|e| EnvError::from(e).named(&self.name));
+            }
+        };
+        visitor
+            .visit_seq(Items {
+                items: items.into_iter(),
+                name: self.name.clone(),
+            })
+            .map_err(|e| e.named(&self.name))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EnvError> {
+        self.value
+            .deserialize_enum(name, variants, visitor)
+            .map_err(|e| EnvError::from(e).named(&self.name))
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct newtype_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct Fields {
+    fields: serde_json::map::IntoIter,
+    value: Option<EnvDeserializer>,
+    prefix: String,
+}
+
+impl<'de> MapAccess<'de> for Fields {
+    type Error = EnvError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, EnvError> {
+        let Some((key, value)) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.value = Some(EnvDeserializer::new(value, env_name(&self.prefix, &key)));
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, EnvError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| <EnvError as de::Error>::custom("value requested before its key"))?;
+        seed.deserialize(value)
+    }
+}
+
+struct Items {
+    items: std::vec::IntoIter<Value>,
+    name: String,
+}
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = EnvError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, EnvError> {
+        self.items
+            .next()
+            .map(|value| seed.deserialize(EnvDeserializer::new(value, self.name.clone())))
+            .transpose()
+    }
+}
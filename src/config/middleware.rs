@@ -1,24 +1,43 @@
 use axum::http::Method;
 use axum::{
-    extract::Request,
-    http::{HeaderValue, Uri},
+    extract::{
+        ConnectInfo, FromRequestParts, MatchedPath, Request, State,
+        ws::{CloseFrame, Message, WebSocketUpgrade},
+    },
+    http::{HeaderMap, HeaderValue, Uri},
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use reqwest::header::CACHE_CONTROL;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use eyre::Result;
+use reqwest::header::{AUTHORIZATION, CACHE_CONTROL, CONNECTION, RETRY_AFTER, WWW_AUTHENTICATE};
 use tower_http::cors::{Any, CorsLayer};
 
 use reqwest::StatusCode;
-use time::UtcOffset;
+use serde::{Deserialize, Serialize};
 
-use std::time::Instant;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tower_http::set_header::SetResponseHeaderLayer;
 use tracing::info_span;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_futures::Instrument;
-use tracing_subscriber::fmt::time::OffsetTime;
-use uuid::Uuid;
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
 
-#[derive(Debug, PartialEq)]
+use crate::config::app_config::{
+    AppConfig, LogConfig, LogFormat, LogRotation, RateLimitConfig, app_config,
+};
+use crate::misc::{
+    metrics::METRICS,
+    request_id::{REQUEST_ID_HEADER, request_id_from, scope_request_id, with_request_id},
+    shutdown::is_shutting_down,
+    utils::constant_time_eq,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Env {
     Development,
     Production,
@@ -27,12 +46,7 @@ pub enum Env {
 
 impl Env {
     pub fn current() -> Self {
-        match std::env::var("ENV").expect("ENV is not set").as_str() {
-            "development" => Self::Development,
-            "production" => Self::Production,
-            "test" => Self::Test,
-            _ => panic!("Invalid ENV"),
-        }
+        app_config().env
     }
 
     pub fn is_dev(&self) -> bool {
@@ -55,16 +69,14 @@ pub async fn request_tracing(request: Request, next: Next) -> Response {
     hotpath::gauge!("requests_count").inc(1);
     hotpath::dbg!(&path);
 
-    let uuid = Uuid::new_v4();
-    let uuid_str = uuid.to_string().replace("-", "");
-    let request_id = &uuid_str[0..12];
+    let request_id = request_id_from(request.headers());
     let method = request.method().clone();
 
     let info_span = info_span!("req", id = %request_id, method = %method, path = %path);
 
     async move {
         let start = Instant::now();
-        let response = next.run(request).await;
+        let mut response = scope_request_id(request_id.clone(), next.run(request)).await;
         let duration = start.elapsed();
 
         tracing::info!(
@@ -72,12 +84,342 @@ pub async fn request_tracing(request: Request, next: Next) -> Response {
             duration_ms = duration.as_millis(),
         );
 
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
         response
     }
     .instrument(info_span)
     .await
 }
 
+/// Counts requests and their latency per route into [`METRICS`]. Paths
+/// matching no route share a label, so scanners can't add series.
+pub async fn record_metrics(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let method = match *request.method() {
+        Method::GET | Method::HEAD | Method::POST | Method::OPTIONS => request.method().as_str(),
+        _ => "OTHER",
+    }
+    .to_string();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    METRICS.record_request(&route, &method, response.status().as_u16(), start.elapsed());
+    response
+}
+
+/// `Retry-After` of requests rejected while draining.
+const DRAIN_RETRY_AFTER_SECS: u64 = 5;
+
+/// Routes sharing a rate limit budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    Html,
+    Json,
+    WebSocket,
+}
+
+impl RouteClass {
+    /// `None` for routes that aren't limited, like static assets.
+    pub fn of(path: &str) -> Option<Self> {
+        if path == "/uptime"
+            || path.starts_with("/assets/")
+            || STATIC_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+        {
+            None
+        } else if path.starts_with("/ws/") {
+            Some(Self::WebSocket)
+        } else if path.starts_with("/api/") || path.starts_with("/sse/") {
+            Some(Self::Json)
+        } else {
+            Some(Self::Html)
+        }
+    }
+}
+
+/// Requests a client may burst, refilled evenly over a minute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    pub per_minute: u32,
+}
+
+impl Budget {
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.per_minute) / BUDGET_WINDOW.as_secs_f64()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Outcome of taking a token, turned into `RateLimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until a token is available again.
+    pub retry_after: u64,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+}
+
+/// Time a [`Budget`] takes to refill completely.
+const BUDGET_WINDOW: Duration = Duration::from_secs(60);
+/// Buckets tracked before idle ones are dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token buckets per client IP, with a separate budget per [`RouteClass`].
+pub struct RateLimiter {
+    budgets: HashMap<RouteClass, Budget>,
+    buckets: Mutex<HashMap<(RouteClass, IpAddr), Bucket>>,
+    /// See [`client_ip`].
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimiter {
+    pub fn new(budgets: HashMap<RouteClass, Budget>) -> Self {
+        Self {
+            budgets,
+            buckets: Mutex::new(HashMap::new()),
+            trusted_proxies: vec![],
+        }
+    }
+
+    /// Budgets of the `[rate_limit]` config, `0` turns off limiting of
+    /// that class.
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        let budgets = [
+            (RouteClass::Html, config.html_per_min),
+            (RouteClass::Json, config.json_per_min),
+            (RouteClass::WebSocket, config.ws_per_min),
+        ]
+        .into_iter()
+        .filter(|(_, per_minute)| *per_minute > 0)
+        .map(|(class, per_minute)| (class, Budget { per_minute }))
+        .collect();
+        Self {
+            trusted_proxies: config
+                .trusted_proxies
+                .iter()
+                .filter_map(|proxy| proxy.parse().ok())
+                .collect(),
+            ..Self::new(budgets)
+        }
+    }
+
+    /// Takes a token from the client's bucket for `class`. `None` when the
+    /// class isn't limited.
+    pub fn check(&self, class: RouteClass, ip: IpAddr) -> Option<RateLimitStatus> {
+        self.check_at(class, ip, Instant::now())
+    }
+
+    fn check_at(&self, class: RouteClass, ip: IpAddr, now: Instant) -> Option<RateLimitStatus> {
+        let budget = *self.budgets.get(&class)?;
+        let capacity = f64::from(budget.per_minute);
+        let refill = budget.refill_per_sec();
+
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            // Buckets idle for a minute are full again, nothing to remember
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < BUDGET_WINDOW);
+        }
+
+        let bucket = buckets.entry((class, ip)).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Some(RateLimitStatus {
+            allowed,
+            limit: budget.per_minute,
+            remaining: bucket.tokens.floor() as u32,
+            retry_after: if allowed {
+                0
+            } else {
+                ((1.0 - bucket.tokens) / refill).ceil() as u64
+            },
+            reset: ((capacity - bucket.tokens) / refill).ceil() as u64,
+        })
+    }
+}
+
+/// The client's address as nginx saw it: `X-Real-IP`, else the last
+/// `X-Forwarded-For` entry, which nginx appends itself, else the peer.
+/// The headers are only believed from loopback and `trusted_proxies`, any
+/// client reaching the port directly could set them.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let unknown = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    let Some(peer) = peer else {
+        return unknown;
+    };
+    if !peer.is_loopback() && !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    header("x-real-ip")
+        .and_then(|value| value.trim().parse().ok())
+        .or_else(|| {
+            header("x-forwarded-for")?
+                .rsplit(',')
+                .next()
+                .and_then(|value| value.trim().parse().ok())
+        })
+        .unwrap_or(peer)
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert("RateLimit-Limit", HeaderValue::from(status.limit));
+    headers.insert("RateLimit-Remaining", HeaderValue::from(status.remaining));
+    headers.insert("RateLimit-Reset", HeaderValue::from(status.reset));
+    if !status.allowed {
+        headers.insert("Retry-After", HeaderValue::from(status.retry_after));
+    }
+}
+
+/// Requires the `[admin]` credentials through HTTP basic auth.
+pub async fn admin_auth(
+    State(config): State<Arc<AppConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let credentials = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| BASE64_STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+
+    let authorized = config.admin.password.as_ref().is_some_and(|password| {
+        credentials
+            .as_deref()
+            .and_then(|credentials| credentials.split_once(':'))
+            .is_some_and(|(username, given)| {
+                // Both compared, so a wrong username takes as long as a wrong password
+                let username_ok =
+                    constant_time_eq(username.as_bytes(), config.admin.username.as_bytes());
+                let password_ok = constant_time_eq(given.as_bytes(), password.as_bytes());
+                username_ok & password_ok
+            })
+    });
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(
+                WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Basic realm="mevlog admin", charset="UTF-8""#),
+            )],
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Limits requests per client IP with [`RateLimiter`]. Limited requests get
+/// a `429`, except WebSocket upgrades, which are accepted and closed right
+/// away with a `1013` close frame, so browsers get to see the reason.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(class) = RouteClass::of(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let ip = client_ip(request.headers(), peer, &limiter.trusted_proxies);
+
+    let Some(status) = limiter.check(class, ip) else {
+        return next.run(request).await;
+    };
+
+    if status.allowed {
+        let mut response = next.run(request).await;
+        insert_rate_limit_headers(response.headers_mut(), &status);
+        return response;
+    }
+
+    tracing::warn!(
+        "Rate limited {} on {:?} {}",
+        ip,
+        class,
+        request.uri().path()
+    );
+    let reason = format!("Rate limited, retry in {}s", status.retry_after);
+
+    let mut response = if class == RouteClass::WebSocket {
+        let (mut parts, _body) = request.into_parts();
+        match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+            Ok(ws) => ws
+                .on_upgrade(|mut socket| async move {
+                    let close = Message::Close(Some(CloseFrame {
+                        code: 1013,
+                        reason: reason.into(),
+                    }));
+                    let _ = socket.send(close).await;
+                })
+                .into_response(),
+            Err(_) => (StatusCode::TOO_MANY_REQUESTS, reason).into_response(),
+        }
+    } else if class == RouteClass::Json {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            axum::Json(with_request_id(serde_json::json!({ "error": reason }))),
+        )
+            .into_response()
+    } else {
+        (StatusCode::TOO_MANY_REQUESTS, reason).into_response()
+    };
+    insert_rate_limit_headers(response.headers_mut(), &status);
+    response
+}
+
+/// Answers `503` once shutdown began, so the proxy and clients retry
+/// elsewhere while in-flight requests finish.
+pub async fn reject_when_draining(request: Request, next: Next) -> Response {
+    if !is_shutting_down() {
+        return next.run(request).await;
+    }
+
+    let reason = "Server restarting, retry shortly";
+    let mut response = if RouteClass::of(request.uri().path()) == Some(RouteClass::Json) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            axum::Json(with_request_id(serde_json::json!({ "error": reason }))),
+        )
+            .into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, reason).into_response()
+    };
+    let headers = response.headers_mut();
+    headers.insert(RETRY_AFTER, HeaderValue::from(DRAIN_RETRY_AFTER_SECS));
+    headers.insert(CONNECTION, HeaderValue::from_static("close"));
+    response
+}
+
 pub async fn security_headers(request: Request, next: Next) -> Response {
     let mut response = next.run(request).await;
 
@@ -148,35 +490,140 @@ pub async fn only_ssl(request: Request, next: Next) -> Response {
     }
 }
 
-pub fn init_logs(filename: &str) {
-    match Env::current() {
-        Env::Production => {
-            let file_appender = tracing_appender::rolling::never("./", filename);
+/// Log timestamps in the `[log] timezone`, following its DST changes.
+struct ZonedTimer(jiff::tz::TimeZone);
 
-            let offset = UtcOffset::from_hms(2, 0, 0).expect("should get CET offset");
-            let time_format =
-                time::format_description::parse("[year]-[month]-[day]T[hour]:[minute]:[second]")
-                    .unwrap();
-            let timer = OffsetTime::new(offset, time_format);
+impl FormatTime for ZonedTimer {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        let now = jiff::Timestamp::now().to_zoned(self.0.clone());
+        write!(w, "{}", now.strftime("%Y-%m-%dT%H:%M:%S%.3f%:z"))
+    }
+}
 
-            tracing_subscriber::fmt()
-                .with_writer(file_appender)
-                .with_timer(timer)
-                .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-                .init();
-        }
-        _ => {
-            let filter = tracing_subscriber::EnvFilter::from_default_env();
+/// Logs to stdout, or in production to `filename` in the configured
+/// directory, rotated and pruned per [`LogConfig`].
+pub fn init_logs(config: &LogConfig, filename: &str) -> Result<()> {
+    let timezone = config.timezone().expect("validated with the config");
+    let subscriber = tracing_subscriber::fmt()
+        .with_timer(ZonedTimer(timezone))
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env());
 
-            tracing_subscriber::fmt().with_env_filter(filter).init()
+    if Env::current() != Env::Production {
+        match config.format {
+            LogFormat::Text => subscriber.init(),
+            LogFormat::Json => subscriber.json().init(),
         }
+        return Ok(());
+    }
+
+    let rotation = match config.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let mut appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(filename);
+    if config.max_files > 0 {
+        appender = appender.max_log_files(config.max_files);
     }
+    let appender = appender.build(&config.dir)?;
+
+    let subscriber = subscriber.with_writer(appender).with_ansi(false);
+    match config.format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+    Ok(())
 }
 
 pub fn host() -> String {
-    match Env::current() {
-        Env::Development | Env::Test => "http://localhost:3000",
-        Env::Production => "https://mevlog.rs",
+    app_config().host()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_minute: u32) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(RouteClass::Json, Budget { per_minute })]))
+    }
+
+    #[test]
+    fn classifies_routes() {
+        assert_eq!(RouteClass::of("/ws/search"), Some(RouteClass::WebSocket));
+        assert_eq!(RouteClass::of("/api/explore"), Some(RouteClass::Json));
+        assert_eq!(RouteClass::of("/sse/search"), Some(RouteClass::Json));
+        assert_eq!(RouteClass::of("/search"), Some(RouteClass::Html));
+        assert_eq!(RouteClass::of("/assets/logo.png"), None);
+        assert_eq!(RouteClass::of("/uptime"), None);
+    }
+
+    #[test]
+    fn limits_each_client_separately() {
+        let limiter = limiter(2);
+        let now = Instant::now();
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert!(limiter.check_at(RouteClass::Json, a, now).unwrap().allowed);
+        assert!(limiter.check_at(RouteClass::Json, a, now).unwrap().allowed);
+        let limited = limiter.check_at(RouteClass::Json, a, now).unwrap();
+        assert!(!limited.allowed);
+        assert_eq!(limited.retry_after, 30);
+        assert_eq!(limited.remaining, 0);
+
+        assert!(limiter.check_at(RouteClass::Json, b, now).unwrap().allowed);
+        assert!(limiter.check(RouteClass::Html, a).is_none());
+
+        // One token is back after half a minute
+        let later = now + Duration::from_secs(30);
+        assert!(
+            limiter
+                .check_at(RouteClass::Json, a, later)
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn reads_client_ip_from_proxy_headers() {
+        let peer: IpAddr = "127.0.0.1".parse().unwrap();
+        let peer_ip = Some(peer);
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_ip(&headers, peer_ip, &[]), peer);
+
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("1.1.1.1, 2.2.2.2"),
+        );
+        assert_eq!(
+            client_ip(&headers, peer_ip, &[]),
+            "2.2.2.2".parse::<IpAddr>().unwrap()
+        );
+
+        headers.insert("x-real-ip", HeaderValue::from_static("3.3.3.3"));
+        assert_eq!(
+            client_ip(&headers, peer_ip, &[]),
+            "3.3.3.3".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn ignores_proxy_headers_from_untrusted_peers() {
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-real-ip", HeaderValue::from_static("3.3.3.3"));
+        headers.insert("x-forwarded-for", HeaderValue::from_static("4.4.4.4"));
+
+        assert_eq!(client_ip(&headers, Some(peer), &[]), peer);
+        assert_eq!(
+            client_ip(&headers, None, &[]),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        );
+        assert_eq!(
+            client_ip(&headers, Some(peer), &[peer]),
+            "3.3.3.3".parse::<IpAddr>().unwrap()
+        );
     }
-    .to_string()
 }
@@ -1,20 +1,24 @@
-use crate::{controllers::*, misc::utils::deployed_at};
+use std::sync::Arc;
+
+use crate::{config::app_config::AppConfig, controllers::*};
 use axum::{
     Router,
     body::Body,
+    extract::State,
     http::{HeaderMap, HeaderValue, Response, StatusCode},
+    middleware::from_fn_with_state,
     response::IntoResponse,
     routing::get,
 };
 use tower::Layer;
 use tower_http::services::{ServeDir, ServeFile};
 
-use super::{cache_control, host};
+use super::{admin_auth, cache_control};
 
-pub async fn app() -> Router {
-    let deployed_at = deployed_at();
+pub async fn app(config: Arc<AppConfig>) -> Router {
+    let deployed_at = config.deployed_at.clone();
 
-    Router::new()
+    let router = Router::new()
         .route("/", get(html::home_controller::home))
         .route("/search", get(html::search_controller::search))
         .route("/terms", get(html::terms_controller::terms))
@@ -26,6 +30,10 @@ pub async fn app() -> Router {
         )
         .route("/api/chains", get(json::chains_controller::chains))
         .route("/api/explore", get(json::explore_controller::explore))
+        .route("/api/search", get(json::search_controller::search))
+        .route("/api/status", get(json::status_controller::status))
+        .route("/sse/search", get(sse::search_controller::search))
+        .route("/ws/blocks", get(websocket::blocks_controller::ws_handler))
         .route("/ws/search", get(websocket::search_controller::ws_handler))
         .route("/uptime", get(|| async move { "OK".into_response() }))
         .route("/robots.txt", get(robots_txt))
@@ -81,11 +89,32 @@ pub async fn app() -> Router {
             "/media/mevlog-tui-demo.mp4",
             cache_control().layer(ServeFile::new("media/mevlog-tui-demo.mp4")),
         )
-        .fallback(html::not_found_controller::not_found)
+        .fallback(html::not_found_controller::not_found);
+
+    let router = if config.metrics.token.is_some() {
+        router.route("/metrics", get(metrics_controller::metrics))
+    } else {
+        router
+    };
+    let router = if config.admin.password.is_some() {
+        router.route(
+            "/admin/status",
+            get(html::admin_status_controller::status)
+                .route_layer(from_fn_with_state(config.clone(), admin_auth)),
+        )
+    } else {
+        router
+    };
+    router.with_state(config)
 }
 
-async fn robots_txt() -> Response<Body> {
-    let h = host();
+/// Served on the admin port, see [`crate::config::app_config::MetricsConfig`].
+pub fn admin_app() -> Router {
+    Router::new().route("/metrics", get(metrics_controller::admin_metrics))
+}
+
+async fn robots_txt(State(config): State<Arc<AppConfig>>) -> Response<Body> {
+    let h = config.host();
     let body = format!("User-agent: *\nAllow: /\n\nSitemap: {h}/sitemap.xml\n");
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -95,8 +124,8 @@ async fn robots_txt() -> Response<Body> {
     (StatusCode::OK, headers, body).into_response()
 }
 
-async fn sitemap_xml() -> Response<Body> {
-    let h = host();
+async fn sitemap_xml(State(config): State<Arc<AppConfig>>) -> Response<Body> {
+    let h = config.host();
     let body = format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
 <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
@@ -157,13 +186,49 @@ pub fn json_response(body: String, status: StatusCode) -> Response<Body> {
 pub mod tests {
 
     use super::*;
+    use crate::config::app_config::app_config;
+    use crate::config::app_config::{AdminConfig, MetricsConfig};
+    use crate::config::middleware::{
+        Budget, RateLimiter, RouteClass, rate_limit, record_metrics, request_tracing,
+    };
+    use crate::misc::{
+        fixture_runner::FixtureRunner,
+        mevlog_cmd::set_runner,
+        prices::{StaticFileSource, set_price_sources},
+    };
     use axum::http::Request;
     use eyre::Result;
+    use futures::StreamExt;
     use http_body_util::BodyExt;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::sync::Arc;
     use tower::ServiceExt;
 
     pub async fn get_test_app() -> Result<Router> {
-        Ok(app().await)
+        get_test_app_with(app_config().clone()).await
+    }
+
+    async fn get_test_app_with(config: AppConfig) -> Result<Router> {
+        set_runner(Arc::new(FixtureRunner::from_dir(env!(
+            "CARGO_MANIFEST_DIR"
+        ))?));
+        set_price_sources(vec![Arc::new(StaticFileSource::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/prices.json"
+        )))]);
+        Ok(app(Arc::new(config)).await)
+    }
+
+    async fn get(uri: &str) -> Result<(StatusCode, String)> {
+        let app = get_test_app().await?;
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty())?)
+            .await?;
+
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, String::from_utf8(body.to_vec())?))
     }
 
     #[tokio::test]
@@ -185,4 +250,552 @@ pub mod tests {
         assert_eq!(body, "OK");
         Ok(())
     }
+
+    #[tokio::test]
+    async fn search_page_test() -> Result<()> {
+        let (status, body) = get("/search?blocks=10:latest&chain_id=10").await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("data-blocks=\"10:latest\""));
+        assert!(body.contains("data-chain-id=\"10\""));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_page_validation_test() -> Result<()> {
+        let (status, body) = get("/search?blocks=20:10&tx_cost=lots").await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("blocks: range start 20 is after range end 10"));
+        assert!(body.contains("tx_cost: must be a comparison"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_page_escapes_validation_errors_test() -> Result<()> {
+        let (status, body) = get(
+            "/search?blocks=%3Cscript%3Ealert(1)%3C%2Fscript%3E&position=1:%3Cimg%20src=x%20onerror=alert(1)%3E",
+        )
+        .await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!body.contains("<script>alert(1)</script>"));
+        assert!(!body.contains("<img src=x"));
+        assert!(body.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chain_info_test() -> Result<()> {
+        let (status, body) = get("/api/chain-info?chain_id=10").await?;
+
+        assert_eq!(status, StatusCode::OK);
+        let chain_info: Value = serde_json::from_str(&body)?;
+        assert_eq!(chain_info["chain_id"], 10);
+        assert_eq!(chain_info["currency"], "ETH");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chain_info_invalid_params_test() -> Result<()> {
+        let (status, body) = get("/api/chain-info?chain_id=abc").await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_str(&body)?;
+        assert!(error["error"].is_string());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_id_test() -> Result<()> {
+        let app = get_test_app()
+            .await?
+            .layer(axum::middleware::from_fn(request_tracing));
+
+        let request = Request::builder()
+            .uri("/api/chain-info?chain_id=abc")
+            .header("x-request-id", "lb-1234")
+            .body(Body::empty())?;
+        let response = app.clone().oneshot(request).await?;
+        assert_eq!(response.headers()["x-request-id"], "lb-1234");
+        let body = response.into_body().collect().await?.to_bytes();
+        let error: Value = serde_json::from_slice(&body)?;
+        assert_eq!(error["request_id"], "lb-1234");
+
+        let request = Request::builder()
+            .uri("/api/chain-info?chain_id=10")
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        assert_eq!(response.headers()["x-request-id"].len(), 12);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn explore_test() -> Result<()> {
+        let (status, body) = get("/api/explore?chain_id=10&block_number=22045570").await?;
+
+        assert_eq!(status, StatusCode::OK);
+        let txs: Vec<Value> = serde_json::from_str(&body)?;
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0]["block_number"], 22045570);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn explore_etag_test() -> Result<()> {
+        let uri = "/api/explore?chain_id=10&block_number=22045571";
+        let app = get_test_app().await?;
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty())?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key("cache-control"));
+        assert!(response.headers().contains_key("x-rpc-url"));
+        assert_eq!(response.headers()["x-rpc-attempts"], "1");
+        let etag = response.headers()["etag"].clone();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header("if-none-match", etag)
+                    .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn api_search_test() -> Result<()> {
+        let (status, body) = get("/api/search?chain_id=1&blocks=22045500:22045570").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let lines = body
+            .lines()
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(lines.len(), 3);
+        assert!(lines[..2].iter().all(|tx| tx["tx_hash"].is_string()));
+
+        let summary = &lines[2]["summary"];
+        assert_eq!(summary["matches"], 2);
+        assert_eq!(summary["blocks"], "22045500:22045570");
+        assert_eq!(summary["from_block"], 22045500);
+        assert_eq!(summary["to_block"], 22045570);
+        assert_eq!(summary["first_match_block"], 22045570);
+        assert_eq!(summary["last_match_block"], 22045570);
+        assert_eq!(summary["timed_out"], false);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn api_search_validation_test() -> Result<()> {
+        let (status, body) = get("/api/search?blocks=0:latest").await?;
+        let error: Value = serde_json::from_str(&body)?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error["errors"]["blocks"].is_string());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn custom_rpc_url_validation_test() -> Result<()> {
+        let (status, body) = get("/api/search?rpc_url=file:///etc/passwd").await?;
+        let error: Value = serde_json::from_str(&body)?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error["errors"]["rpc_url"].is_string());
+
+        let (status, body) = get("/api/explore?chain_id=1&rpc_url=gopher://localhost").await?;
+        let error: Value = serde_json::from_str(&body)?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error["error"].as_str().unwrap().contains("rpc_url"));
+        Ok(())
+    }
+
+    fn sse_events(body: &str) -> Vec<(String, Option<String>)> {
+        body.split("\n\n")
+            .filter_map(|frame| {
+                let field = |name: &str| {
+                    frame
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::to_string)
+                };
+                Some((field("event: ")?, field("id: ")))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sse_search_test() -> Result<()> {
+        let (status, body) = get("/sse/search?chain_id=1&blocks=22045500:22045570").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        assert_eq!(
+            sse_events(&body),
+            vec![
+                ("progress".to_string(), None),
+                ("progress".to_string(), None),
+                ("result".to_string(), Some("22045570:0".to_string())),
+                ("result".to_string(), Some("22045570:15".to_string())),
+                ("done".to_string(), None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sse_search_rejects_malformed_query_test() -> Result<()> {
+        let (status, body) = get("/sse/search?chain_id=abc").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        assert_eq!(
+            sse_events(&body),
+            vec![("error".to_string(), None), ("done".to_string(), None)]
+        );
+        assert!(body.contains("chain_id"), "{body}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sse_search_resume_test() -> Result<()> {
+        let app = get_test_app().await?;
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sse/search?chain_id=1&blocks=22045500:22045570")
+                    .header("Last-Event-ID", "22045570:0")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(
+            sse_events(&String::from_utf8(body.to_vec())?),
+            vec![
+                ("progress".to_string(), None),
+                ("progress".to_string(), None),
+                ("result".to_string(), Some("22045570:15".to_string())),
+                ("done".to_string(), None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn status_test() -> Result<()> {
+        let (status, body) = get("/api/status").await?;
+
+        assert_eq!(status, StatusCode::OK);
+        let status: Value = serde_json::from_str(&body)?;
+        assert!(status["mevlog_pool"]["running"].is_u64());
+        assert!(status["mevlog_pool"]["queued"].is_u64());
+        assert!(status["explore_cache"]["hits"].is_u64());
+        Ok(())
+    }
+
+    type TestSocket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn connect_ws(path: &str) -> Result<TestSocket> {
+        connect_ws_to(get_test_app().await?, path).await
+    }
+
+    async fn connect_ws_to(app: Router, path: &str) -> Result<TestSocket> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}{path}")).await?;
+        Ok(socket)
+    }
+
+    async fn send_ws(socket: &mut TestSocket, message: Value) -> Result<()> {
+        use futures::SinkExt;
+        socket
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                message.to_string().into(),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Reads envelopes up to and including the next `done` or `pong`.
+    async fn read_ws(socket: &mut TestSocket) -> Result<Vec<Value>> {
+        let mut messages = vec![];
+        while let Some(msg) = socket.next().await {
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = msg? {
+                let message = serde_json::from_str::<Value>(&text)?;
+                let last = message["type"] == "done" || message["type"] == "pong";
+                messages.push(message);
+                if last {
+                    break;
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    fn types(messages: &[Value]) -> Vec<&str> {
+        messages
+            .iter()
+            .map(|message| message["type"].as_str().unwrap_or_default())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn ws_search_test() -> Result<()> {
+        let mut socket = connect_ws("/ws/search?blocks=22045570&chain_id=10").await?;
+        let messages = read_ws(&mut socket).await?;
+
+        assert_eq!(
+            types(&messages),
+            vec!["progress", "progress", "result", "result", "done"]
+        );
+        assert!(
+            messages
+                .iter()
+                .filter(|message| message["type"] == "result")
+                .all(|message| message["search_id"] == 1 && message["data"]["tx_hash"].is_string())
+        );
+        let done = &messages[4];
+        assert_eq!(done["reason"], "completed");
+        assert_eq!(done["matches"], 2);
+        assert!(done["rpc_url"].is_string());
+        assert_eq!(done["attempts"], 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ws_search_commands_test() -> Result<()> {
+        let mut socket = connect_ws("/ws/search").await?;
+
+        send_ws(&mut socket, serde_json::json!({ "type": "ping" })).await?;
+        assert_eq!(types(&read_ws(&mut socket).await?), vec!["pong"]);
+
+        for id in [7, 8] {
+            send_ws(
+                &mut socket,
+                serde_json::json!({
+                    "type": "search",
+                    "id": id,
+                    "params": { "blocks": "22045570", "chain_id": 1 }
+                }),
+            )
+            .await?;
+            let messages = read_ws(&mut socket).await?;
+            assert_eq!(
+                types(&messages).iter().filter(|t| **t == "result").count(),
+                2
+            );
+            assert!(messages.iter().all(|message| message["search_id"] == id));
+        }
+
+        send_ws(&mut socket, serde_json::json!({ "type": "unknown" })).await?;
+        let Some(Ok(tokio_tungstenite::tungstenite::Message::Text(text))) = socket.next().await
+        else {
+            panic!("expected an error message");
+        };
+        let error: Value = serde_json::from_str(&text)?;
+        assert_eq!(error["type"], "error");
+        assert!(error["search_id"].is_null());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ws_blocks_validation_test() -> Result<()> {
+        let mut socket = connect_ws("/ws/blocks?chain_id=1&blocks=10:latest&to=nobody").await?;
+
+        let Some(Ok(tokio_tungstenite::tungstenite::Message::Text(text))) = socket.next().await
+        else {
+            panic!("expected a validation error message");
+        };
+        let error: Value = serde_json::from_str(&text)?;
+        assert_eq!(error["type"], "error");
+        assert!(error["errors"]["blocks"].is_string());
+        assert!(error["errors"]["to"].is_string());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ws_search_validation_test() -> Result<()> {
+        let mut socket = connect_ws("/ws/search").await?;
+        send_ws(
+            &mut socket,
+            serde_json::json!({
+                "type": "search",
+                "params": { "blocks": "1:latest", "from": "not-an-address" }
+            }),
+        )
+        .await?;
+
+        let messages = read_ws(&mut socket).await?;
+        assert_eq!(types(&messages), vec!["error", "done"]);
+        assert!(messages[0]["errors"]["from"].is_string());
+        assert!(messages[0]["errors"]["blocks"].is_null());
+        assert_eq!(messages[1]["reason"], "failed");
+        Ok(())
+    }
+
+    fn rate_limited_app(app: Router, class: RouteClass) -> Router {
+        let limiter = RateLimiter::new(HashMap::from([(class, Budget { per_minute: 1 })]));
+        app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(limiter),
+            rate_limit,
+        ))
+    }
+
+    #[tokio::test]
+    async fn rate_limit_test() -> Result<()> {
+        let app = rate_limited_app(get_test_app().await?, RouteClass::Json);
+        let request = || {
+            Request::builder()
+                .uri("/api/chain-info?chain_id=10")
+                .header("x-real-ip", "10.0.0.1")
+                .body(Body::empty())
+        };
+
+        let response = app.clone().oneshot(request()?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["RateLimit-Remaining"], "0");
+
+        let response = app.oneshot(request()?).await?;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["Retry-After"], "60");
+        assert_eq!(response.headers()["RateLimit-Limit"], "1");
+        let body = response.into_body().collect().await?.to_bytes();
+        let body: Value = serde_json::from_slice(&body)?;
+        assert!(body["error"].as_str().unwrap().contains("Rate limited"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ws_rate_limit_test() -> Result<()> {
+        let app = rate_limited_app(get_test_app().await?, RouteClass::WebSocket);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let url = format!("ws://{addr}/ws/search");
+
+        let (_allowed, _) = tokio_tungstenite::connect_async(&url).await?;
+        let (mut limited, _) = tokio_tungstenite::connect_async(&url).await?;
+
+        let Some(Ok(tokio_tungstenite::tungstenite::Message::Close(Some(frame)))) =
+            limited.next().await
+        else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(u16::from(frame.code), 1013);
+        assert!(frame.reason.contains("Rate limited"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn metrics_test() -> Result<()> {
+        let config = AppConfig {
+            metrics: MetricsConfig {
+                token: Some("a-long-metrics-token".to_string()),
+                ..MetricsConfig::default()
+            },
+            ..app_config().clone()
+        };
+        let app = get_test_app_with(config)
+            .await?
+            .layer(axum::middleware::from_fn(record_metrics));
+        let request = |token: Option<&str>| {
+            let mut request = Request::builder().uri("/metrics");
+            if let Some(token) = token {
+                request = request.header("Authorization", format!("Bearer {token}"));
+            }
+            request.body(Body::empty())
+        };
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/terms").body(Body::empty())?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(request(None)?).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(request(Some("wrong"))?).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.oneshot(request(Some("a-long-metrics-token"))?).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = String::from_utf8(body.to_vec())?;
+        assert!(body.contains(r#"http_requests_total{route="/terms",method="GET",status="200"}"#));
+        assert!(body.contains("# TYPE websockets_active gauge"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn metrics_need_token_test() -> Result<()> {
+        let app = get_test_app().await?;
+        let response = app
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty())?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn admin_status_test() -> Result<()> {
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        let config = AppConfig {
+            admin: AdminConfig {
+                username: "ops".to_string(),
+                password: Some("a-long-admin-password".to_string()),
+            },
+            ..app_config().clone()
+        };
+        let app = get_test_app_with(config).await?;
+        let request = |credentials: Option<&str>| {
+            let mut request = Request::builder().uri("/admin/status");
+            if let Some(credentials) = credentials {
+                let encoded = BASE64_STANDARD.encode(credentials);
+                request = request.header("Authorization", format!("Basic {encoded}"));
+            }
+            request.body(Body::empty())
+        };
+
+        let response = app.clone().oneshot(request(None)?).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(
+            response.headers()["WWW-Authenticate"]
+                .to_str()?
+                .starts_with("Basic")
+        );
+        let response = app
+            .clone()
+            .oneshot(request(Some("admin:a-long-admin-password"))?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(request(Some("ops:a-long-admin-password"))?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Cache-Control"], "no-store");
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = String::from_utf8(body.to_vec())?;
+        assert!(body.contains("Running mevlog processes"));
+        assert!(body.contains("Scheduler jobs"));
+
+        let response = get_test_app()
+            .await?
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/status")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
 }
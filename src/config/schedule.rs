@@ -1,87 +1,90 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use eyre::Result;
 use tokio_cron_scheduler::{Job, JobScheduler};
 
-use crate::misc::{prices::update_prices_cache, utils::uptime_ping};
+use crate::config::app_config::AppConfig;
+use crate::misc::{
+    price_history::update_price_history, prices::update_prices_cache,
+    rpc_prober::update_probe_table, scheduler_status::record_job_run, utils::uptime_ping,
+};
 
-pub async fn get_schedule() -> Result<JobScheduler> {
+pub async fn get_schedule(config: Arc<AppConfig>) -> Result<JobScheduler> {
     let mut sched = JobScheduler::new().await?;
 
+    if let Some(uptime_url) = config.uptime_url.scheduler.clone() {
+        sched
+            .add(Job::new_async("every 10 minutes", move |_uuid, _l| {
+                let uptime_url = uptime_url.clone();
+                Box::pin(async move {
+                    tracing::info!("Scheduler uptime ping");
+                    let result = uptime_ping(&uptime_url).await;
+                    if let Err(e) = &result {
+                        tracing::error!("Failed to uptime ping: {}", e);
+                    }
+                    record_job_run("uptime_ping", result.map_err(|e| e.to_string())).await;
+                })
+            })?)
+            .await?;
+    }
+
     sched
-        .add(Job::new_async("every 10 minutes", |_uuid, _l| {
+        .add(Job::new_async("every 5 minutes", |_uuid, _l| {
             Box::pin(async move {
-                tracing::info!("Scheduler uptime ping");
-                let uptime_url = std::env::var("UPTIME_URL_SCHEDULER")
-                    .expect("Missing UPTIME_URL_SCHEDULER env var");
-                match uptime_ping(&uptime_url).await {
-                    Ok(_) => {}
+                let result = update_prices_cache().await;
+                match &result {
+                    Ok(_) => {
+                        tracing::info!("Prices cache updated");
+                    }
                     Err(e) => {
-                        tracing::error!("Failed to uptime ping: {}", &e);
+                        tracing::error!("Failed to update prices cache: {}", e);
                     }
-                };
+                }
+                record_job_run("prices_cache", result.map_err(|e| e.to_string())).await;
             })
         })?)
         .await?;
 
+    // Backfilled right away, then topped up every hour
+    let symbols = config.price_history.symbols.clone();
     sched
-        .add(Job::new_async("every 5 minutes", |_uuid, _l| {
+        .add(Job::new_one_shot_async(
+            Duration::ZERO,
+            move |_uuid, _l| Box::pin(update_price_history_job(symbols.clone())),
+        )?)
+        .await?;
+
+    let symbols = config.price_history.symbols.clone();
+    sched
+        .add(Job::new_async("0 7 * * * *", move |_uuid, _l| {
+            Box::pin(update_price_history_job(symbols.clone()))
+        })?)
+        .await?;
+
+    sched
+        .add(Job::new_async("every 2 minutes", move |_uuid, _l| {
+            let config = config.clone();
             Box::pin(async move {
-                match update_prices_cache().await {
+                let result = update_probe_table(&config.rpc_probe.chains).await;
+                match &result {
                     Ok(_) => {
-                        tracing::info!("Prices cache updated");
+                        tracing::info!("RPC probe table updated");
+                        if let Some(uptime_url) = &config.uptime_url.rpc_prober
+                            && let Err(e) = uptime_ping(uptime_url).await
+                        {
+                            tracing::error!("Failed to uptime ping: {}", &e);
+                        }
                     }
                     Err(e) => {
-                        tracing::error!("Failed to update prices cache: {}", &e);
+                        tracing::error!("Failed to update RPC probe table: {}", e);
                     }
                 }
+                record_job_run("rpc_probe", result.map_err(|e| e.to_string())).await;
             })
         })?)
         .await?;
 
-    // sched
-    //     .add(Job::new_async("every 2 minutes", |_uuid, _l| {
-    //         Box::pin(async move {
-    //             tracing::info!("Block number check");
-    //             let local_rpc_url =
-    //                 std::env::var("ETH_RPC_URL_VAL").expect("Missing ETH_RPC_URL_VAL env var");
-    //             let local_provider = ProviderBuilder::new()
-    //                 .on_http(local_rpc_url.parse().expect("Invalid local RPC URL"));
-
-    //             let remote_rpc_url = std::env::var("ETH_RPC_URL_REMOTE")
-    //                 .expect("Missing ETH_RPC_URL_REMOTE env var");
-    //             let remote_provider = ProviderBuilder::new()
-    //                 .on_http(remote_rpc_url.parse().expect("Invalid remote RPC URL"));
-
-    //             let local_block_number = match local_provider.get_block_number().await {
-    //                 Ok(n) => n,
-    //                 Err(e) => {
-    //                     tracing::error!("Failed to get local block number: {}", e);
-    //                     return;
-    //                 }
-    //             };
-    //             let remote_block_number = match remote_provider.get_block_number().await {
-    //                 Ok(n) => n,
-    //                 Err(e) => {
-    //                     tracing::error!("Failed to get remote block number: {}", e);
-    //                     return;
-    //                 }
-    //             };
-
-    //             if local_block_number + 1 >= remote_block_number {
-    //                 let uptime_url = std::env::var("UPTIME_URL_BLOCK_NUMBER")
-    //                     .expect("Missing UPTIME_URL_BLOCK_NUMBER env var");
-    //                 tracing::info!("Blocks number matching ping");
-
-    //                 match uptime_ping(&uptime_url).await {
-    //                     Ok(_) => {}
-    //                     Err(e) => {
-    //                         error!("Failed to uptime ping: {}", &e);
-    //                     }
-    //                 }
-    //             }
-    //         })
-    //     })?)
-    //     .await?;
-
     // sched
     //     .add(Job::new_async("every 30 minutes", |_uuid, _l| {
     //         Box::pin(async move {
@@ -137,8 +140,6 @@ pub async fn get_schedule() -> Result<JobScheduler> {
     //     })?)
     //     .await?;
 
-    sched.shutdown_on_ctrl_c();
-
     sched.set_shutdown_handler(Box::new(|| {
         Box::pin(async move {
             tracing::info!("Shut down done");
@@ -147,3 +148,16 @@ pub async fn get_schedule() -> Result<JobScheduler> {
 
     Ok(sched)
 }
+
+async fn update_price_history_job(symbols: Vec<String>) {
+    let result = update_price_history(&symbols).await;
+    match &result {
+        Ok(_) => {
+            tracing::info!("Price history updated");
+        }
+        Err(e) => {
+            tracing::error!("Failed to update price history: {}", e);
+        }
+    }
+    record_job_run("price_history", result.map_err(|e| e.to_string())).await;
+}
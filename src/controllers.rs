@@ -1,4 +1,6 @@
 pub mod base_controller;
 pub mod html;
 pub mod json;
+pub mod metrics_controller;
+pub mod sse;
 pub mod websocket;
@@ -1,3 +1,4 @@
+pub mod admin_status_controller;
 pub mod explore_controller;
 pub mod home_controller;
 pub mod not_found_controller;
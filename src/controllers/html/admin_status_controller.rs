@@ -0,0 +1,143 @@
+use askama::Template;
+use axum::{
+    extract::State,
+    http::{HeaderValue, header::CACHE_CONTROL},
+    response::IntoResponse,
+};
+use reqwest::StatusCode;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::{
+    config::{app_config::AppConfig, routes::html_response},
+    misc::{
+        mevlog_cmd::running_processes,
+        prices::{PriceStats, price_stats},
+        rpc_utils::{RpcUrlCacheStats, rpc_url_cache_stats},
+        scheduler_status::scheduler_status,
+        utils::unix_now,
+    },
+};
+
+struct ProcessRow {
+    args: String,
+    chain_id: String,
+    elapsed: String,
+}
+
+struct JobRow {
+    name: String,
+    last_run: String,
+    last_success: String,
+    last_error: String,
+}
+
+struct WarmerRow {
+    chain_id: u64,
+    head_block: String,
+    updated: String,
+    last_error: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin_status.html")]
+struct AdminStatusTemplate {
+    host: String,
+    page: String,
+    deployed_at: String,
+    title: String,
+    description: String,
+    canonical_url: String,
+    env: String,
+    version: String,
+    deployed: String,
+    processes: Vec<ProcessRow>,
+    rpc_caches: Vec<RpcUrlCacheStats>,
+    prices: Vec<PriceStats>,
+    jobs: Vec<JobRow>,
+    warmers: Vec<WarmerRow>,
+}
+
+/// What the server and the scheduler are doing right now, so it doesn't
+/// take an ssh session and the logs to find out.
+pub async fn status(State(config): State<Arc<AppConfig>>) -> impl IntoResponse {
+    let processes = running_processes()
+        .into_iter()
+        .map(|process| ProcessRow {
+            args: process.args.join(" "),
+            chain_id: process
+                .chain_id
+                .map_or_else(|| "-".to_string(), |id| id.to_string()),
+            elapsed: format!("{:.1}s", process.started_at.elapsed().as_secs_f64()),
+        })
+        .collect();
+    let (prices, _) = price_stats().await;
+
+    let scheduler = scheduler_status().await;
+    let jobs = scheduler
+        .jobs
+        .into_iter()
+        .map(|(name, job)| JobRow {
+            name,
+            last_run: format_timestamp(Some(job.last_run)),
+            last_success: format_timestamp(job.last_success),
+            last_error: job.last_error.unwrap_or_default(),
+        })
+        .collect();
+    let warmers = scheduler
+        .warmers
+        .into_iter()
+        .map(|(chain_id, warmer)| WarmerRow {
+            chain_id,
+            head_block: warmer
+                .head_block
+                .map_or_else(|| "-".to_string(), |block| block.to_string()),
+            updated: format_timestamp(Some(warmer.updated_at)),
+            last_error: warmer.last_error.unwrap_or_default(),
+        })
+        .collect();
+
+    let h = config.host();
+    let template = AdminStatusTemplate {
+        title: "Status - mevlog.rs".to_string(),
+        description: "Internal status of mevlog.rs.".to_string(),
+        canonical_url: format!("{h}/admin/status"),
+        host: h,
+        page: "admin".to_string(),
+        deployed_at: config.deployed_at.clone(),
+        env: format!("{:?}", config.env).to_lowercase(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        deployed: format_timestamp(config.deployed_at.parse().ok()),
+        processes,
+        rpc_caches: rpc_url_cache_stats().await,
+        prices,
+        jobs,
+        warmers,
+    };
+
+    let mut response = html_response(template.render().unwrap(), StatusCode::OK);
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// UTC time and how long ago it was, `-` when there's none.
+fn format_timestamp(timestamp: Option<u64>) -> String {
+    let Some(datetime) =
+        timestamp.and_then(|ts| OffsetDateTime::from_unix_timestamp(ts as i64).ok())
+    else {
+        return "-".to_string();
+    };
+    let ago = unix_now().saturating_sub(datetime.unix_timestamp() as u64);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02} UTC ({}s ago)",
+        datetime.year(),
+        u8::from(datetime.month()),
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+        ago
+    )
+}
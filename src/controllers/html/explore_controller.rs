@@ -1,9 +1,12 @@
-use crate::config::{host, routes::html_response};
+use crate::config::{app_config::AppConfig, routes::html_response};
 use crate::controllers::json::explore_controller::ExploreParams;
-use crate::misc::utils::deployed_at;
 use askama::Template;
-use axum::{extract::Query, response::IntoResponse};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
 use reqwest::StatusCode;
+use std::sync::Arc;
 
 #[derive(Template)]
 #[template(path = "explore.html")]
@@ -19,8 +22,8 @@ struct ExploreTemplate {
 }
 
 impl ExploreTemplate {
-    pub fn new(chain_id: Option<u64>, block_number: Option<String>) -> Self {
-        let h = host();
+    pub fn new(config: &AppConfig, chain_id: Option<u64>, block_number: Option<String>) -> Self {
+        let h = config.host();
         let canonical_url = format!("{h}/explore");
         Self {
             title: "Explore EVM Blocks - mevlog.rs".to_string(),
@@ -28,7 +31,7 @@ impl ExploreTemplate {
             canonical_url,
             host: h,
             page: "explore".to_string(),
-            deployed_at: deployed_at(),
+            deployed_at: config.deployed_at.clone(),
             chain_id,
             block_number,
         }
@@ -44,7 +47,10 @@ impl ExploreTemplate {
 }
 
 #[hotpath::measure]
-pub async fn explore(Query(params): Query<ExploreParams>) -> impl IntoResponse {
+pub async fn explore(
+    State(config): State<Arc<AppConfig>>,
+    Query(params): Query<ExploreParams>,
+) -> impl IntoResponse {
     let chain_id = if params.chain_id == Some(1) {
         None
     } else {
@@ -56,6 +62,6 @@ pub async fn explore(Query(params): Query<ExploreParams>) -> impl IntoResponse {
         params.block_number
     };
 
-    let template = ExploreTemplate::new(chain_id, block_number);
+    let template = ExploreTemplate::new(&config, chain_id, block_number);
     html_response(template.render().unwrap(), StatusCode::OK)
 }
@@ -1,11 +1,9 @@
 use askama::Template;
-use axum::response::IntoResponse;
+use axum::{extract::State, response::IntoResponse};
 use reqwest::StatusCode;
+use std::sync::Arc;
 
-use crate::{
-    config::{host, routes::html_response},
-    misc::utils::deployed_at,
-};
+use crate::config::{app_config::AppConfig, routes::html_response};
 
 // force html views recompilation by changing this value
 const _VIEW_VERSION: u64 = 17;
@@ -22,17 +20,17 @@ struct HomeTemplate {
 }
 
 #[hotpath::measure]
-pub async fn home() -> impl IntoResponse {
+pub async fn home(State(config): State<Arc<AppConfig>>) -> impl IntoResponse {
     tracing::debug!("Home controller called");
 
-    let h = host();
+    let h = config.host();
     let template = HomeTemplate {
         title: "mevlog.rs - Explore EVM chains in one place, powered by Revm".to_string(),
         description: "Open-source web interface for querying EVM transactions across 2000+ chains. Search by events, method calls, ENS names, ERC20 transfers, and more.".to_string(),
         canonical_url: format!("{h}/"),
         host: h,
         page: "home".to_string(),
-        deployed_at: deployed_at(),
+        deployed_at: config.deployed_at.clone(),
     };
 
     html_response(template.render().unwrap(), StatusCode::OK)
@@ -1,10 +1,8 @@
-use crate::{
-    config::{host, routes::html_response},
-    misc::utils::deployed_at,
-};
+use crate::config::{app_config::AppConfig, routes::html_response};
 use askama::Template;
-use axum::response::IntoResponse;
+use axum::{extract::State, response::IntoResponse};
 use reqwest::StatusCode;
+use std::sync::Arc;
 
 #[derive(Template)]
 #[template(path = "404.html")]
@@ -17,15 +15,15 @@ struct NotFoundTemplate {
     canonical_url: String,
 }
 
-pub async fn not_found() -> impl IntoResponse {
-    let h = host();
+pub async fn not_found(State(config): State<Arc<AppConfig>>) -> impl IntoResponse {
+    let h = config.host();
     let template = NotFoundTemplate {
         title: "Page Not Found - mevlog.rs".to_string(),
         description: "The page you are looking for does not exist.".to_string(),
         canonical_url: format!("{h}/"),
         host: h,
         page: "404".to_string(),
-        deployed_at: deployed_at(),
+        deployed_at: config.deployed_at.clone(),
     };
 
     html_response(template.render().unwrap(), StatusCode::NOT_FOUND)
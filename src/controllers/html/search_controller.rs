@@ -1,12 +1,19 @@
-use crate::config::{host, routes::html_response};
+use crate::config::{app_config::AppConfig, routes::html_response};
 use crate::controllers::base_controller::empty_string_as_none;
 use crate::controllers::json::base_controller::extract_query_params;
-use crate::misc::utils::deployed_at;
+use crate::misc::custom_rpc::RPC_POLICY;
+use crate::misc::validation::{
+    ValidationErrors, validate_address_or_ens, validate_blocks, validate_comparison,
+    validate_erc20_transfer, validate_event, validate_method, validate_position, validate_to,
+};
 use askama::Template;
-use axum::{extract::Query, response::IntoResponse};
-use eyre::Result;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
 use reqwest::StatusCode;
 use serde::Deserialize;
+use std::sync::Arc;
 
 use crate::controllers::base_controller::{error_message, get_default_blocks};
 
@@ -14,6 +21,7 @@ use crate::controllers::base_controller::{error_message, get_default_blocks};
 #[template(path = "search.html")]
 struct SearchTemplate {
     output: String,
+    errors: Vec<String>,
     blocks: String,
     position: String,
     from: String,
@@ -34,13 +42,19 @@ struct SearchTemplate {
 }
 
 impl SearchTemplate {
-    pub fn new(params: SearchParams, output: String) -> Self {
+    pub fn new(
+        config: &AppConfig,
+        params: SearchParams,
+        output: String,
+        errors: Vec<String>,
+    ) -> Self {
         let blocks = get_default_blocks(params.blocks);
-        let h = host();
+        let h = config.host();
         let canonical_url = format!("{h}/search");
 
         Self {
             output,
+            errors,
             blocks,
             position: params.position.unwrap_or_default(),
             from: params.from.unwrap_or_default(),
@@ -53,7 +67,7 @@ impl SearchTemplate {
             gas_price: params.gas_price.unwrap_or_default(),
             host: h,
             page: "search".to_string(),
-            deployed_at: deployed_at(),
+            deployed_at: config.deployed_at.clone(),
             chain_id: params.chain_id.unwrap_or(1).to_string(),
             title: "Search EVM Transactions - mevlog.rs".to_string(),
             description: "Search and filter EVM transactions with advanced queries. Filter by events, method calls, ENS names, validator bribes, ERC20 transfers, gas price, and more.".to_string(),
@@ -85,16 +99,74 @@ pub struct SearchParams {
     #[serde(default, deserialize_with = "empty_string_as_none")]
     pub gas_price: Option<String>,
     pub chain_id: Option<u64>,
+    /// Runs the search against this RPC instead of a public one.
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub rpc_url: Option<String>,
 }
 
 impl SearchParams {
-    pub async fn validate(&self) -> Result<()> {
-        Ok(())
+    pub fn is_empty(&self) -> bool {
+        self.chain_id.is_none()
+            && [
+                &self.blocks,
+                &self.position,
+                &self.from,
+                &self.to,
+                &self.event,
+                &self.not_event,
+                &self.method,
+                &self.erc20_transfer,
+                &self.tx_cost,
+                &self.gas_price,
+            ]
+            .iter()
+            .all(|value| value.is_none())
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        if let Some(blocks) = &self.blocks {
+            errors.check("blocks", validate_blocks(blocks));
+        }
+        if let Some(position) = &self.position {
+            errors.check("position", validate_position(position));
+        }
+        if let Some(from) = &self.from {
+            errors.check("from", validate_address_or_ens(from));
+        }
+        if let Some(to) = &self.to {
+            errors.check("to", validate_to(to));
+        }
+        if let Some(event) = &self.event {
+            errors.check("event", validate_event(event));
+        }
+        if let Some(not_event) = &self.not_event {
+            errors.check("not_event", validate_event(not_event));
+        }
+        if let Some(method) = &self.method {
+            errors.check("method", validate_method(method));
+        }
+        if let Some(erc20_transfer) = &self.erc20_transfer {
+            errors.check("erc20_transfer", validate_erc20_transfer(erc20_transfer));
+        }
+        if let Some(tx_cost) = &self.tx_cost {
+            errors.check("tx_cost", validate_comparison(tx_cost));
+        }
+        if let Some(gas_price) = &self.gas_price {
+            errors.check("gas_price", validate_comparison(gas_price));
+        }
+        if let Some(rpc_url) = &self.rpc_url {
+            errors.check("rpc_url", RPC_POLICY.check_url(rpc_url).map(|_| ()));
+        }
+
+        errors.into_result()
     }
 }
 
 #[hotpath::measure]
 pub async fn search(
+    State(config): State<Arc<AppConfig>>,
     query: Result<Query<SearchParams>, axum::extract::rejection::QueryRejection>,
 ) -> impl IntoResponse {
     let params = match extract_query_params(query) {
@@ -102,12 +174,13 @@ pub async fn search(
         Err(e) => return error_message(&e).into_response(),
     };
 
-    let (output, status) = match params.validate().await {
-        Ok(_) => ("<div style='color: #888; padding: 20px; text-align: center; font-family: monospace;'>Press search to query</div>".to_string(), StatusCode::OK),
-        Err(e) => (error_message(&e.to_string()), StatusCode::BAD_REQUEST),
+    // Validation messages quote the input, the template escapes them
+    let (output, errors, status) = match params.validate() {
+        Ok(_) => ("<div style='color: #888; padding: 20px; text-align: center; font-family: monospace;'>Press search to query</div>".to_string(), vec![], StatusCode::OK),
+        Err(errors) => (String::new(), errors.messages(), StatusCode::BAD_REQUEST),
     };
 
-    let template = SearchTemplate::new(params, output);
+    let template = SearchTemplate::new(&config, params, output, errors);
 
     html_response(template.render().unwrap(), status)
 }
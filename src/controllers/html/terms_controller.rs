@@ -1,10 +1,8 @@
-use crate::{
-    config::{host, routes::html_response},
-    misc::utils::deployed_at,
-};
+use crate::config::{app_config::AppConfig, routes::html_response};
 use askama::Template;
-use axum::response::IntoResponse;
+use axum::{extract::State, response::IntoResponse};
 use reqwest::StatusCode;
+use std::sync::Arc;
 
 #[derive(Template)]
 #[template(path = "terms.html")]
@@ -18,15 +16,15 @@ struct TermsTemplate {
 }
 
 #[hotpath::measure]
-pub async fn terms() -> impl IntoResponse {
-    let h = host();
+pub async fn terms(State(config): State<Arc<AppConfig>>) -> impl IntoResponse {
+    let h = config.host();
     let template = TermsTemplate {
         title: "Privacy Policy & Terms - mevlog.rs".to_string(),
         description: "Privacy policy and terms of use for mevlog.rs.".to_string(),
         canonical_url: format!("{h}/terms"),
         host: h,
         page: "terms".to_string(),
-        deployed_at: deployed_at(),
+        deployed_at: config.deployed_at.clone(),
     };
 
     html_response(template.render().unwrap(), StatusCode::OK)
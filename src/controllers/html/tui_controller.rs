@@ -1,10 +1,8 @@
-use crate::{
-    config::{host, routes::html_response},
-    misc::utils::deployed_at,
-};
+use crate::config::{app_config::AppConfig, routes::html_response};
 use askama::Template;
-use axum::response::IntoResponse;
+use axum::{extract::State, response::IntoResponse};
 use reqwest::StatusCode;
+use std::sync::Arc;
 
 #[derive(Template)]
 #[template(path = "tui.html")]
@@ -18,15 +16,15 @@ struct TuiTemplate {
 }
 
 #[hotpath::measure]
-pub async fn tui() -> impl IntoResponse {
-    let h = host();
+pub async fn tui(State(config): State<Arc<AppConfig>>) -> impl IntoResponse {
+    let h = config.host();
     let template = TuiTemplate {
         title: "TUI Terminal Interface - mevlog.rs".to_string(),
         description: "Query EVM transactions from your terminal with mevlog-rs TUI. Vim-style navigation, multi-chain support, flexible filters, and EVM tracing insights.".to_string(),
         canonical_url: format!("{h}/tui"),
         host: h,
         page: "tui".to_string(),
-        deployed_at: deployed_at(),
+        deployed_at: config.deployed_at.clone(),
     };
 
     html_response(template.render().unwrap(), StatusCode::OK)
@@ -2,3 +2,5 @@ pub mod base_controller;
 pub mod chain_info_controller;
 pub mod chains_controller;
 pub mod explore_controller;
+pub mod search_controller;
+pub mod status_controller;
@@ -1,10 +1,10 @@
 use axum::{Json, extract::Query, http::StatusCode, response::IntoResponse};
 use mevlog::ChainInfoNoRpcsJson;
 use serde::Deserialize;
-use tokio::process::Command as AsyncCommand;
 
-use crate::controllers::json::base_controller::{
-    call_json_command_first_line, extract_json_query_params,
+use crate::{
+    controllers::json::base_controller::extract_json_query_params,
+    misc::mevlog_cmd::{MevlogCmd, MevlogError, run_json_first_line},
 };
 
 #[derive(Debug, Deserialize)]
@@ -13,21 +13,21 @@ pub struct ChainInfoParams {
 }
 
 #[hotpath::measure(log = true)]
-pub async fn fetch_chain_info_no_rpcs(chain_id: u64) -> Result<ChainInfoNoRpcsJson, String> {
-    let mut cmd = AsyncCommand::new("mevlog");
-    cmd.arg("chain-info")
+pub async fn fetch_chain_info_no_rpcs(chain_id: u64) -> Result<ChainInfoNoRpcsJson, MevlogError> {
+    let mut cmd = MevlogCmd::new("chain-info");
+    cmd.chain(chain_id)
         .arg("--chain-id")
         .arg(chain_id.to_string())
         .arg("--format")
         .arg("json")
         .arg("--skip-urls");
-    cmd.env("RUST_LOG", "off");
 
-    match call_json_command_first_line::<ChainInfoNoRpcsJson>(&mut cmd).await {
+    match run_json_first_line::<ChainInfoNoRpcsJson>(&cmd).await {
         Ok(chain_info) => Ok(chain_info),
-        Err(e) => Err(format!(
+        Err(MevlogError::Failed(e)) => Err(MevlogError::Failed(format!(
             "Failed to get chain info for chain_id {chain_id}: {e}",
-        )),
+        ))),
+        Err(e) => Err(e),
     }
 }
 
@@ -44,6 +44,8 @@ pub async fn chain_info(
 
     match fetch_chain_info_no_rpcs(params.chain_id).await {
         Ok(chain_info) => (StatusCode::OK, Json(chain_info)).into_response(),
-        Err(error_json) => (StatusCode::BAD_REQUEST, Json(error_json)).into_response(),
+        Err(e @ MevlogError::Busy { .. }) => e.into_response(),
+        // The viewers expect the message as a bare JSON string
+        Err(e) => (StatusCode::BAD_REQUEST, Json(e.to_string())).into_response(),
     }
 }
@@ -1,10 +1,18 @@
-use axum::{Json, extract::Query, http::StatusCode, response::IntoResponse};
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
 use mevlog::ChainEntryJson;
 use serde::Deserialize;
-use tokio::process::Command as AsyncCommand;
 
-use crate::controllers::json::base_controller::{
-    call_json_command_first_line, extract_json_query_params,
+use crate::{
+    config::app_config::AppConfig,
+    controllers::json::base_controller::extract_json_query_params,
+    misc::mevlog_cmd::{MevlogCmd, run_json_first_line},
 };
 
 #[derive(Debug, Deserialize)]
@@ -16,6 +24,7 @@ pub struct ChainsParams {
 
 #[hotpath::measure(log = true)]
 pub async fn chains(
+    State(config): State<Arc<AppConfig>>,
     query: Result<Query<ChainsParams>, axum::extract::rejection::QueryRejection>,
 ) -> impl IntoResponse {
     let params = match extract_json_query_params(query) {
@@ -23,9 +32,8 @@ pub async fn chains(
         Err(error_response) => return error_response.into_response(),
     };
 
-    let mut cmd = AsyncCommand::new("mevlog");
-    cmd.arg("chains").arg("--format").arg("json");
-    cmd.env("RUST_LOG", "off");
+    let mut cmd = MevlogCmd::new("chains");
+    cmd.arg("--format").arg("json");
 
     if let Some(filter) = &params.filter {
         cmd.arg("--filter").arg(filter);
@@ -39,14 +47,13 @@ pub async fn chains(
         cmd.arg("--chain-id").arg(chain_id.to_string());
     } else if params.filter.is_none() && params.limit.is_none() {
         // If no parameters are provided, return default popular chains
-        let default_chain_ids = [1, 137, 8453, 10, 130, 43114, 56, 42161];
-        for chain_id in default_chain_ids {
+        for chain_id in &config.chains.default_ids {
             cmd.arg("--chain-id").arg(chain_id.to_string());
         }
     }
 
-    match call_json_command_first_line::<Vec<ChainEntryJson>>(&mut cmd).await {
+    match run_json_first_line::<Vec<ChainEntryJson>>(&cmd).await {
         Ok(chains) => (StatusCode::OK, Json(chains)).into_response(),
-        Err(error_json) => (StatusCode::BAD_REQUEST, Json(error_json)).into_response(),
+        Err(e) => e.into_response(),
     }
 }
@@ -1,14 +1,15 @@
-use axum::{Json, extract::Query, http::StatusCode, response::IntoResponse};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::IntoResponse,
+};
 use serde::Deserialize;
-use tokio::process::Command as AsyncCommand;
 
 use crate::{
-    controllers::json::base_controller::{call_json_command_first_line, extract_json_query_params},
-    misc::{
-        prices::get_price_for_chain_id,
-        rpc_utils::get_random_rpc_url,
-        utils::{measure_end, measure_start},
+    controllers::{
+        base_controller::empty_string_as_none, json::base_controller::extract_json_query_params,
     },
+    misc::explore::explore_block_cached,
 };
 
 #[derive(Debug, Deserialize)]
@@ -16,10 +17,14 @@ pub struct ExploreParams {
     pub chain_id: Option<u64>,
     #[serde(default)]
     pub block_number: Option<String>,
+    /// Reads the block from this RPC instead of a public one.
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub rpc_url: Option<String>,
 }
 
 #[hotpath::measure]
 pub async fn explore(
+    request_headers: HeaderMap,
     query: Result<Query<ExploreParams>, axum::extract::rejection::QueryRejection>,
 ) -> impl IntoResponse {
     let params = match extract_json_query_params(query) {
@@ -31,43 +36,48 @@ pub async fn explore(
 
     let chain_id = params.chain_id.unwrap_or(1);
 
-    let mut cmd = AsyncCommand::new("mevlog");
-    cmd.arg("search")
-        .arg("-b")
-        .arg(
-            params
-                .block_number
-                .map_or("latest".to_string(), |bn| bn.to_string()),
-        )
-        .arg("--format")
-        .arg("json")
-        .arg("--rpc-timeout-ms")
-        .arg("500")
-        .arg("--latest-offset") // Improves caching
-        .arg("1");
-    cmd.env("RUST_LOG", "off");
-
-    let price = get_price_for_chain_id(chain_id).await;
+    let block = match explore_block_cached(chain_id, params.block_number, params.rpc_url).await {
+        Ok(block) => block,
+        Err(e) => return e.into_response(),
+    };
 
-    if let Ok(Some(price)) = price {
-        cmd.arg("--native-token-price").arg(price.to_string());
+    let mut headers = HeaderMap::new();
+    if let Ok(etag) = HeaderValue::from_str(&block.etag) {
+        headers.insert(header::ETAG, etag);
     }
-
-    if let Ok(Some(rpc_url)) = get_random_rpc_url(chain_id).await {
-        cmd.arg("--rpc-url").arg(&rpc_url);
+    if let Ok(cache_control) = HeaderValue::from_str(&block.cache_control()) {
+        headers.insert(header::CACHE_CONTROL, cache_control);
     }
-
-    cmd.arg("--chain-id").arg(chain_id.to_string());
-    cmd.arg("--skip-verify-chain-id");
-
-    tracing::debug!("explore command: {:?}", &cmd);
-
-    let start = measure_start("explore cmd");
-    match call_json_command_first_line::<serde_json::Value>(&mut cmd).await {
-        Ok(explore_data) => {
-            measure_end(start);
-            (StatusCode::OK, Json(explore_data)).into_response()
+    if let Some(served_by) = &block.served_by {
+        if let Ok(rpc_url) = HeaderValue::from_str(&served_by.rpc_url) {
+            headers.insert("x-rpc-url", rpc_url);
         }
-        Err(error_json) => (StatusCode::BAD_REQUEST, Json(error_json)).into_response(),
+        headers.insert("x-rpc-attempts", HeaderValue::from(served_by.attempts));
     }
+    if let Some(price) = &block.price
+        && let Ok(usd) = HeaderValue::from_str(&price.usd.to_string())
+    {
+        headers.insert("x-native-token-price", usd);
+        headers.insert("x-price-timestamp", HeaderValue::from(price.timestamp));
+        if let Some(age_secs) = price.age_secs {
+            headers.insert("x-price-age", HeaderValue::from(age_secs));
+        }
+        if price.stale {
+            headers.insert("x-price-stale", HeaderValue::from_static("true"));
+        }
+    }
+
+    let not_modified = request_headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|etag| etag.trim() == block.etag));
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    (StatusCode::OK, headers, block.body.to_string()).into_response()
 }
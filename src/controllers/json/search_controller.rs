@@ -0,0 +1,185 @@
+use std::time::Instant;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::Query,
+    http::{HeaderValue, StatusCode, header::CONTENT_TYPE},
+    response::IntoResponse,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    controllers::{
+        html::search_controller::SearchParams, json::base_controller::extract_json_query_params,
+    },
+    misc::{
+        mevlog_cmd::{MevlogStream, RunEnd, run_stream},
+        price_history::{PriceQuote, price_for_blocks},
+        request_id::RequestScope,
+        rpc_utils::{fetch_block_number, resolve_rpc_url},
+        search_query::{BlockRange, SearchQuery},
+        search_stream::{SearchEvent, forward_search_events},
+    },
+};
+
+/// Last record of every `/api/search` response.
+#[derive(Debug, Default, Serialize)]
+pub struct SearchSummary {
+    pub matches: u64,
+    /// The `blocks` value that was requested, e.g. `10:latest`.
+    pub blocks: String,
+    /// Block range the search ran over, `None` if `latest` couldn't be
+    /// resolved. Only partly scanned when `timed_out` is set.
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    /// Lowest and highest block that produced a match.
+    pub first_match_block: Option<u64>,
+    pub last_match_block: Option<u64>,
+    pub elapsed_ms: u64,
+    pub timed_out: bool,
+    /// Native token price USD values were computed with, `None` when
+    /// they're left out.
+    pub price: Option<PriceQuote>,
+}
+
+impl SearchSummary {
+    fn record_match(&mut self, tx: &Value) {
+        self.matches += 1;
+        if let Some(block) = tx["block_number"].as_u64() {
+            self.first_match_block = Some(self.first_match_block.map_or(block, |b| b.min(block)));
+            self.last_match_block = Some(self.last_match_block.map_or(block, |b| b.max(block)));
+        }
+    }
+}
+
+/// Pins `latest` to a block number so the summary can report exactly
+/// which blocks were searched.
+async fn resolve_block_range(query: &SearchQuery) -> Option<BlockRange> {
+    if !BlockRange::is_relative(&query.blocks) {
+        return BlockRange::resolve(&query.blocks, None);
+    }
+
+    let rpc_url = query.rpc_url.as_deref()?;
+    match fetch_block_number(rpc_url).await {
+        Ok(head) => {
+            let latest = head.saturating_sub(query.latest_offset.unwrap_or(0));
+            BlockRange::resolve(&query.blocks, Some(latest))
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to resolve latest block for {}: {}",
+                &query.blocks,
+                &e
+            );
+            None
+        }
+    }
+}
+
+/// Same search as `/ws/search`, streamed as newline-delimited JSON: one
+/// transaction per line, `{"error": ...}` lines for mevlog errors, and a
+/// final `{"summary": ...}` line.
+#[hotpath::measure]
+pub async fn search(
+    query: Result<Query<SearchParams>, axum::extract::rejection::QueryRejection>,
+) -> impl IntoResponse {
+    let params = match extract_json_query_params(query) {
+        Ok(params) => params,
+        Err(error_response) => return error_response.into_response(),
+    };
+
+    if let Err(errors) = params.validate() {
+        return (StatusCode::BAD_REQUEST, Json(errors.to_json())).into_response();
+    }
+
+    let mut query = SearchQuery::streaming(&params);
+
+    query.rpc_url = match resolve_rpc_url(query.chain_id, params.rpc_url.as_deref()).await {
+        Ok(rpc_url) => rpc_url,
+        Err(e) => return e.into_response(),
+    };
+
+    let price = price_for_blocks(query.chain_id, &query.blocks).await;
+    query.native_token_price = price.map(|price| price.usd);
+
+    let requested_blocks = query.blocks.clone();
+    let range = resolve_block_range(&query).await;
+    if let Some(range) = range {
+        query.blocks = range.to_string();
+    }
+
+    let cmd = query.to_cmd();
+
+    let mut stream = match run_stream(&cmd).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            tracing::error!("Failed to start mevlog search: {}", &e);
+            return e.into_response();
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<String>(32);
+    tokio::spawn(RequestScope::current().run(async move {
+        let end = stream_ndjson(&mut stream, &tx, requested_blocks, range, price).await;
+        stream.finish(end).await;
+    }));
+
+    let body = Body::from_stream(ReceiverStream::new(rx).map(Ok::<_, std::convert::Infallible>));
+    (
+        StatusCode::OK,
+        [(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-ndjson"),
+        )],
+        body,
+    )
+        .into_response()
+}
+
+/// Writes mevlog output to the response channel, followed by the summary
+/// unless the client already went away.
+async fn stream_ndjson(
+    stream: &mut MevlogStream,
+    tx: &mpsc::Sender<String>,
+    blocks: String,
+    range: Option<BlockRange>,
+    price: Option<PriceQuote>,
+) -> RunEnd {
+    let started_at = Instant::now();
+    let mut summary = SearchSummary {
+        blocks,
+        price,
+        from_block: range.map(|range| range.from),
+        to_block: range.map(|range| range.to),
+        ..Default::default()
+    };
+
+    let end = forward_search_events(stream, tx, |event| {
+        match &event {
+            SearchEvent::Match(tx_json) => summary.record_match(tx_json),
+            SearchEvent::TimedOut => summary.timed_out = true,
+            SearchEvent::Error(_) => {}
+        }
+        Some(ndjson_line(&event.to_json()))
+    })
+    .await;
+
+    if end != RunEnd::ClientDisconnected {
+        summary.elapsed_ms = started_at.elapsed().as_millis() as u64;
+        let summary = serde_json::json!({ "summary": summary });
+        if tx.send(ndjson_line(&summary)).await.is_err() {
+            return RunEnd::ClientDisconnected;
+        }
+    }
+
+    end
+}
+
+fn ndjson_line(value: &Value) -> String {
+    format!("{value}\n")
+}
@@ -0,0 +1,20 @@
+use axum::{Json, http::StatusCode, response::IntoResponse};
+
+use crate::misc::{
+    explore_cache::EXPLORE_CACHE, mevlog_pool::MEVLOG_POOL, prices::price_stats, rpc_pool::RPC_POOL,
+};
+
+#[hotpath::measure]
+pub async fn status() -> impl IntoResponse {
+    let (prices, price_sources) = price_stats().await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "mevlog_pool": MEVLOG_POOL.stats(),
+            "explore_cache": EXPLORE_CACHE.stats(),
+            "rpc_pool": RPC_POOL.stats(),
+            "prices": prices,
+            "price_sources": price_sources,
+        })),
+    )
+}
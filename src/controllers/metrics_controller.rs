@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    config::app_config::AppConfig,
+    misc::{
+        metrics::{METRICS, MetricsText},
+        prices::price_stats,
+        rpc_utils::rpc_url_cache_stats,
+        scheduler_status::scheduler_status,
+        utils::constant_time_eq,
+    },
+};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// `/metrics` on the main port, only answered with the configured token.
+pub async fn metrics(State(config): State<Arc<AppConfig>>, headers: HeaderMap) -> Response {
+    let authorized = config.metrics.token.as_ref().is_some_and(|token| {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    });
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))],
+        )
+            .into_response();
+    }
+
+    metrics_response().await
+}
+
+/// `/metrics` on the admin port, which only the monitoring can reach.
+pub async fn admin_metrics() -> Response {
+    metrics_response().await
+}
+
+async fn metrics_response() -> Response {
+    let mut out = MetricsText::default();
+    METRICS.write_to(&mut out);
+
+    out.header(
+        "rpc_url_cache_age_seconds",
+        "gauge",
+        "Age of each chain's cached RPC list.",
+    );
+    for cached in rpc_url_cache_stats().await {
+        out.sample(
+            "rpc_url_cache_age_seconds",
+            &[("chain_id", &cached.chain_id.to_string())],
+            cached.age.as_secs(),
+        );
+    }
+
+    out.header(
+        "price_cache_age_seconds",
+        "gauge",
+        "Age of each cached native token price.",
+    );
+    let (prices, _) = price_stats().await;
+    for price in prices {
+        out.sample(
+            "price_cache_age_seconds",
+            &[("symbol", &price.symbol)],
+            price.age_secs,
+        );
+    }
+
+    out.header(
+        "scheduler_job_last_success_timestamp_seconds",
+        "gauge",
+        "When each scheduler job last succeeded, as a Unix timestamp.",
+    );
+    for (job, status) in scheduler_status().await.jobs {
+        if let Some(timestamp) = status.last_success {
+            out.sample(
+                "scheduler_job_last_success_timestamp_seconds",
+                &[("job", &job)],
+                timestamp,
+            );
+        }
+    }
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE))],
+        out.into_string(),
+    )
+        .into_response()
+}
@@ -0,0 +1 @@
+pub mod search_controller;
@@ -0,0 +1,210 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::Query,
+    http::HeaderMap,
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::controllers::html::search_controller::SearchParams;
+use crate::controllers::json::base_controller::extract_query_params;
+use crate::misc::{
+    request_id::{RequestScope, with_request_id},
+    search_query::SearchQuery,
+    search_stream::{SearchEvent, SearchProgress, SearchUpdate, run_search},
+};
+
+type EventSender = mpsc::Sender<Result<Event, Infallible>>;
+
+/// Position of a transaction, sent as the SSE `id:` so a reconnecting client
+/// can tell us where it stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct EventId {
+    block: u64,
+    index: u64,
+}
+
+impl EventId {
+    fn parse(value: &str) -> Option<Self> {
+        let (block, index) = value.split_once(':')?;
+        Some(Self {
+            block: block.parse().ok()?,
+            index: index.parse().ok()?,
+        })
+    }
+
+    fn of(tx: &Value) -> Option<Self> {
+        Some(Self {
+            block: tx["block_number"].as_u64()?,
+            index: tx["index"].as_u64()?,
+        })
+    }
+}
+
+impl std::fmt::Display for EventId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.block, self.index)
+    }
+}
+
+/// The `/ws/search` stream for clients that can't open WebSockets, run
+/// by the same [`run_search`], failing over to other RPCs. Emits a `price`
+/// event with the native token price USD values are computed with, if any,
+/// `progress` events with the matches so far, `result`, `error` and
+/// `timeout` events and a final `done`. Invalid params get an `error` and
+/// `done` right away.
+///
+/// Results carry a `<block>:<index>` id. When the browser reconnects with
+/// `Last-Event-ID`, an explicit `N:M` block range is narrowed to start at
+/// that block, and results up to and including that transaction are
+/// skipped.
+#[hotpath::measure]
+pub async fn search(
+    query: Result<Query<SearchParams>, axum::extract::rejection::QueryRejection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let (tx, rx) = mpsc::channel(32);
+    let last_event_id = headers
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(EventId::parse);
+
+    tokio::spawn(RequestScope::current().run(async move {
+        match extract_query_params(query) {
+            Ok(params) => stream_events(params, last_event_id, tx).await,
+            Err(e) => send_error_and_done(&tx, with_request_id(json!({ "error": e }))).await,
+        }
+    }));
+
+    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
+
+async fn stream_events(params: SearchParams, resume_after: Option<EventId>, tx: EventSender) {
+    if let Err(errors) = params.validate() {
+        send_error_and_done(&tx, errors.to_json()).await;
+        return;
+    }
+
+    let mut query = SearchQuery::streaming(&params);
+    if let Some(last) = resume_after {
+        query.blocks = resume_blocks(&query.blocks, last.block);
+        tracing::info!(
+            "Resuming SSE search after {} at blocks {}",
+            last,
+            query.blocks
+        );
+    }
+
+    // Dropped when the client goes away, which stops the search
+    let (_cancel, cancel_rx) = oneshot::channel();
+    let (updates_tx, mut updates) = mpsc::channel(32);
+    tokio::spawn(RequestScope::current().run(run_search(
+        query,
+        params.rpc_url,
+        updates_tx,
+        cancel_rx,
+    )));
+
+    let mut progress = SearchProgress::default();
+    loop {
+        let (events, finished) = match updates.recv().await {
+            Some(SearchUpdate::Started { served_by, price }) => {
+                let mut events = vec![];
+                if progress.price.is_none()
+                    && let Some(price) = price
+                    && let Ok(data) = serde_json::to_string(&price)
+                {
+                    events.push(Event::default().event("price").data(data));
+                }
+                progress.served_by = served_by;
+                progress.price = price;
+                events.push(progress_event(&progress));
+                (events, false)
+            }
+            Some(SearchUpdate::Event(SearchEvent::Match(tx_json))) => {
+                let id = EventId::of(&tx_json);
+                if let (Some(id), Some(last)) = (id, resume_after)
+                    && id <= last
+                {
+                    continue;
+                }
+                let mut events = vec![];
+                if progress.record_match(&tx_json) {
+                    events.push(progress_event(&progress));
+                }
+                let result = Event::default().event("result").data(tx_json.to_string());
+                events.push(match id {
+                    Some(id) => result.id(id.to_string()),
+                    None => result,
+                });
+                (events, false)
+            }
+            Some(SearchUpdate::Event(event)) => {
+                let name = match event {
+                    SearchEvent::TimedOut => "timeout",
+                    _ => "error",
+                };
+                let data = event.to_json().to_string();
+                (vec![Event::default().event(name).data(data)], false)
+            }
+            Some(SearchUpdate::Failed(e)) => {
+                send_error_and_done(&tx, e.to_json()).await;
+                return;
+            }
+            Some(SearchUpdate::Finished(end)) => (
+                vec![Event::default().event("done").data(end.to_string())],
+                true,
+            ),
+            // The search task ended without reporting, e.g. it panicked
+            None => (vec![Event::default().event("done").data("failed")], true),
+        };
+
+        for event in events {
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
+        }
+        if finished {
+            return;
+        }
+    }
+}
+
+fn progress_event(progress: &SearchProgress) -> Event {
+    let data = json!({
+        "matches": progress.matches,
+        "last_block": progress.last_block,
+        "elapsed_ms": progress.elapsed_ms(),
+    });
+    Event::default().event("progress").data(data.to_string())
+}
+
+async fn send_error_and_done(tx: &EventSender, error: Value) {
+    let events = [
+        Event::default().event("error").data(error.to_string()),
+        Event::default().event("done").data("failed"),
+    ];
+    for event in events {
+        if tx.send(Ok(event)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Narrows an explicit `N:M` range to start at `block`. Relative values like
+/// `10:latest` are kept, already delivered results are skipped instead.
+fn resume_blocks(blocks: &str, block: u64) -> String {
+    if let Some((start, end)) = blocks.split_once(':')
+        && let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>())
+        && (start..=end).contains(&block)
+    {
+        return format!("{block}:{end}");
+    }
+    blocks.to_string()
+}
@@ -1,2 +1,3 @@
 pub mod base_controller;
+pub mod blocks_controller;
 pub mod search_controller;
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        Query,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::IntoResponse,
+};
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::controllers::html::search_controller::SearchParams;
+use crate::controllers::websocket::base_controller::{close_for_restart, send_json};
+use crate::misc::{
+    block_watcher::{BlockUpdate, subscribe},
+    explore::run_block_query,
+    metrics::METRICS,
+    mevlog_cmd::MevlogError,
+    request_id::RequestScope,
+    search_query::{SearchFilters, SearchFormat, SearchQuery},
+    shutdown::shutdown_started,
+    validation::ValidationErrors,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Ping,
+    Pong,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Transactions of a new block, only the matching ones when filters
+    /// were given.
+    Block {
+        chain_id: u64,
+        block_number: u64,
+        txs: Value,
+    },
+    Error {
+        block_number: Option<u64>,
+        error: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        errors: Option<ValidationErrors>,
+    },
+    Pong,
+}
+
+impl ServerMessage {
+    fn error(block_number: Option<u64>, error: impl Into<String>) -> Self {
+        Self::Error {
+            block_number,
+            error: error.into(),
+            errors: None,
+        }
+    }
+}
+
+#[hotpath::measure]
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<SearchParams>,
+) -> impl IntoResponse {
+    let scope = RequestScope::current();
+    ws.on_upgrade(|socket| scope.run(handle_socket(socket, params)))
+}
+
+/// Pushes every new block of `chain_id` to the socket. Search filters in
+/// the query string are applied to each block before it's sent.
+#[hotpath::measure]
+async fn handle_socket(socket: WebSocket, params: SearchParams) {
+    let _open = METRICS.websocket_opened();
+    let (mut sender, mut receiver) = socket.split();
+
+    let mut validation = params.validate().err().unwrap_or_default();
+    if params.blocks.is_some() {
+        validation.check(
+            "blocks",
+            Err("not supported, every new block is sent".to_string()),
+        );
+    }
+    if params.rpc_url.is_some() {
+        validation.check(
+            "rpc_url",
+            Err("not supported, blocks are read from public RPCs".to_string()),
+        );
+    }
+    if let Err(errors) = validation.into_result() {
+        let error = ServerMessage::Error {
+            block_number: None,
+            error: errors.to_string(),
+            errors: Some(errors),
+        };
+        send_json(&mut sender, &error).await;
+        return;
+    }
+
+    let chain_id = params.chain_id.unwrap_or(1);
+    let filters = SearchFilters::from(&params);
+    let mut updates = subscribe(chain_id);
+
+    loop {
+        tokio::select! {
+            msg = receiver.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Ping) => ServerMessage::Pong,
+                    Ok(ClientMessage::Pong) => continue,
+                    Err(e) => ServerMessage::error(None, format!("Invalid command: {e}")),
+                };
+                if !send_json(&mut sender, &reply).await {
+                    break;
+                }
+            }
+            update = updates.recv() => {
+                let message = match update {
+                    Ok(update) => block_message(update, &filters).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        ServerMessage::error(None, format!("Skipped {skipped} blocks, the connection is too slow"))
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !send_json(&mut sender, &message).await {
+                    break;
+                }
+            }
+            _ = shutdown_started() => {
+                close_for_restart(&mut sender).await;
+                break;
+            }
+        }
+    }
+
+    tracing::info!("Block subscription for chain {} closed", chain_id);
+}
+
+async fn block_message(update: Arc<BlockUpdate>, filters: &SearchFilters) -> ServerMessage {
+    let txs = if filters.is_empty() {
+        update.txs.clone()
+    } else {
+        filtered_block(&update, filters).await
+    };
+
+    match txs {
+        Ok(txs) => ServerMessage::Block {
+            chain_id: update.chain_id,
+            block_number: update.block_number,
+            txs,
+        },
+        Err(e) => ServerMessage::error(Some(update.block_number), e.to_string()),
+    }
+}
+
+/// Re-runs the block through mevlog with the subscriber's filters, the
+/// block data is already cached by the shared explore run.
+async fn filtered_block(
+    update: &BlockUpdate,
+    filters: &SearchFilters,
+) -> Result<Value, MevlogError> {
+    let query = SearchQuery {
+        format: Some(SearchFormat::Json),
+        filters: filters.clone(),
+        rpc_timeout_ms: Some(500),
+        ..SearchQuery::new(update.chain_id, update.block_number.to_string())
+    };
+    run_block_query(query).await.map(|output| output.txs)
+}
@@ -1,102 +1,351 @@
-use crate::controllers::html::search_controller::SearchParams;
-use crate::controllers::{
-    base_controller::get_default_blocks,
-    websocket::base_controller::{cmd_output_stream, stream_output_lines},
-};
-use crate::misc::rpc_utils::get_random_rpc_url;
 use axum::{
     extract::{
         Query,
-        ws::{WebSocket, WebSocketUpgrade},
+        ws::{Message, WebSocket, WebSocketUpgrade},
     },
     http::HeaderMap,
     response::IntoResponse,
 };
+use futures::stream::{SplitSink, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
 
-use futures::stream::StreamExt;
-use tokio::process::Command;
+use crate::controllers::html::search_controller::SearchParams;
+use crate::controllers::websocket::base_controller::{close_for_restart, send_json};
+use crate::misc::{
+    metrics::METRICS,
+    mevlog_cmd::{MevlogError, RunEnd},
+    price_history::PriceQuote,
+    request_id::RequestScope,
+    rpc_utils::ServedBy,
+    search_query::SearchQuery,
+    search_stream::{SearchEvent, SearchProgress, SearchUpdate, run_search},
+    shutdown::shutdown_started,
+    validation::ValidationErrors,
+};
 
-#[hotpath::measure]
-pub async fn ws_handler(
-    ws: WebSocketUpgrade,
-    Query(params): Query<SearchParams>,
-    headers: HeaderMap,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, params, headers))
+/// Commands a client sends over `/ws/search`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Starts a search, cancelling the one in progress.
+    Search {
+        #[serde(default)]
+        id: Option<u64>,
+        params: Box<SearchParams>,
+    },
+    /// Stops the search in progress.
+    Cancel,
+    Ping,
+    Pong,
 }
 
-#[hotpath::measure]
-async fn handle_socket(socket: WebSocket, params: SearchParams, _headers: HeaderMap) {
-    let (sender, _receiver) = socket.split();
+/// Envelopes the server replies with. Everything about a search carries
+/// its `search_id`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Result {
+        search_id: u64,
+        data: Value,
+    },
+    Progress {
+        search_id: u64,
+        matches: u64,
+        last_block: Option<u64>,
+        elapsed_ms: u64,
+    },
+    Error {
+        search_id: Option<u64>,
+        error: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        errors: Option<ValidationErrors>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after: Option<u64>,
+    },
+    Done {
+        search_id: u64,
+        reason: DoneReason,
+        matches: u64,
+        elapsed_ms: u64,
+        /// The RPC the last attempt ran against, and how many were made.
+        #[serde(flatten)]
+        served_by: Option<ServedBy>,
+        /// Native token price USD values were computed with.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        price: Option<PriceQuote>,
+    },
+    Pong,
+}
 
-    let chain_id = params.chain_id.unwrap_or(1);
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoneReason {
+    Completed,
+    Cancelled,
+    TimedOut,
+    /// The search could not start, an `error` envelope precedes this.
+    Failed,
+}
+
+impl From<RunEnd> for DoneReason {
+    fn from(end: RunEnd) -> Self {
+        match end {
+            RunEnd::Completed | RunEnd::FirstLineRead => Self::Completed,
+            RunEnd::ClientDisconnected | RunEnd::Cancelled | RunEnd::ShuttingDown => {
+                Self::Cancelled
+            }
+            RunEnd::TimedOut => Self::TimedOut,
+        }
+    }
+}
 
-    let mut cmd = Command::new("mevlog");
+impl ServerMessage {
+    fn error(search_id: Option<u64>, error: impl Into<String>) -> Self {
+        Self::Error {
+            search_id,
+            error: error.into(),
+            errors: None,
+            retry_after: None,
+        }
+    }
+}
 
-    cmd.arg("search")
-        .arg("--format")
-        .arg("json-stream")
-        .arg("--latest-offset") // Improves caching
-        .arg("1")
-        .arg("--batch-size")
-        .arg("20")
-        .arg("--max-range")
-        .arg("500");
+struct ActiveSearch {
+    id: u64,
+    cancel: oneshot::Sender<()>,
+    updates: mpsc::Receiver<SearchUpdate>,
+    progress: SearchProgress,
+}
 
-    match get_random_rpc_url(chain_id).await {
-        Ok(Some(rpc_url)) => {
-            cmd.arg("--rpc-url").arg(&rpc_url);
+impl ActiveSearch {
+    fn progress(&self) -> ServerMessage {
+        ServerMessage::Progress {
+            search_id: self.id,
+            matches: self.progress.matches,
+            last_block: self.progress.last_block,
+            elapsed_ms: self.progress.elapsed_ms(),
         }
-        _ => {
-            cmd.arg("--chain-id").arg(chain_id.to_string());
+    }
+
+    fn done(&self, reason: DoneReason) -> ServerMessage {
+        ServerMessage::Done {
+            search_id: self.id,
+            reason,
+            matches: self.progress.matches,
+            elapsed_ms: self.progress.elapsed_ms(),
+            served_by: self.progress.served_by.clone(),
+            price: self.progress.price,
         }
     }
+}
 
-    let blocks = get_default_blocks(params.blocks.clone());
+#[hotpath::measure]
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<SearchParams>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let scope = RequestScope::current();
+    ws.on_upgrade(|socket| scope.run(handle_socket(socket, params, headers)))
+}
 
-    cmd.arg("-b").arg(blocks);
+/// Runs one search at a time per socket. Query params given at upgrade time
+/// start the first search right away, later ones come in as `search`
+/// commands.
+#[hotpath::measure]
+async fn handle_socket(socket: WebSocket, params: SearchParams, _headers: HeaderMap) {
+    let _open = METRICS.websocket_opened();
+    let (mut sender, mut receiver) = socket.split();
+    let mut active: Option<ActiveSearch> = None;
+    let mut next_id = 1;
 
-    if let Some(position) = params.position.clone() {
-        cmd.arg("-p").arg(position);
+    if !params.is_empty() {
+        active = start_search(&mut sender, next_id, params).await;
+        next_id += 1;
     }
 
-    if let Some(from) = params.from.clone() {
-        cmd.arg("--from").arg(from);
-    }
+    loop {
+        tokio::select! {
+            msg = receiver.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
 
-    if let Some(to) = params.to.clone() {
-        cmd.arg("--to").arg(to);
-    }
+                let command = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(command) => command,
+                    Err(e) => {
+                        let error = ServerMessage::error(None, format!("Invalid command: {e}"));
+                        if !send_json(&mut sender, &error).await {
+                            break;
+                        }
+                        continue;
+                    }
+                };
 
-    if let Some(event) = params.event.clone() {
-        cmd.arg("--event").arg(event);
-    }
+                let connected = match command {
+                    ClientMessage::Search { id, params } => {
+                        if let Some(search) = active.take() {
+                            cancel_search(&mut sender, search).await;
+                        }
+                        let id = id.unwrap_or(next_id);
+                        next_id = next_id.max(id) + 1;
+                        active = start_search(&mut sender, id, *params).await;
+                        true
+                    }
+                    ClientMessage::Cancel => match active.take() {
+                        Some(search) => cancel_search(&mut sender, search).await,
+                        None => true,
+                    },
+                    ClientMessage::Ping => send_json(&mut sender, &ServerMessage::Pong).await,
+                    ClientMessage::Pong => true,
+                };
+                if !connected {
+                    break;
+                }
+            }
+            update = next_update(&mut active) => {
+                let Some(search) = active.as_mut() else {
+                    continue;
+                };
 
-    if let Some(not_event) = params.not_event.clone() {
-        cmd.arg("--not-event").arg(not_event);
+                let (messages, finished) = apply_update(search, update);
+                if finished {
+                    active = None;
+                }
+                let mut connected = true;
+                for message in messages {
+                    if !send_json(&mut sender, &message).await {
+                        connected = false;
+                        break;
+                    }
+                }
+                if !connected {
+                    break;
+                }
+            }
+            _ = shutdown_started() => {
+                if let Some(search) = active.take() {
+                    cancel_search(&mut sender, search).await;
+                }
+                close_for_restart(&mut sender).await;
+                break;
+            }
+        }
     }
 
-    if let Some(method) = params.method.clone() {
-        cmd.arg("--method").arg(method);
-    }
+    // Dropping the search closes its cancel channel, which stops the process
+    drop(active);
+    tracing::info!("WebSocket connection closed");
+}
 
-    if let Some(erc20_transfer) = params.erc20_transfer.clone() {
-        cmd.arg("--erc20-transfer").arg(erc20_transfer);
+async fn next_update(active: &mut Option<ActiveSearch>) -> Option<SearchUpdate> {
+    match active {
+        Some(search) => search.updates.recv().await,
+        None => std::future::pending().await,
     }
+}
 
-    if let Some(tx_cost) = params.tx_cost.clone() {
-        cmd.arg("--tx-cost").arg(tx_cost);
+/// Turns an update from the search task into the envelopes sent to the
+/// client, and tells whether the search is over.
+fn apply_update(
+    search: &mut ActiveSearch,
+    update: Option<SearchUpdate>,
+) -> (Vec<ServerMessage>, bool) {
+    match update {
+        Some(SearchUpdate::Started { served_by, price }) => {
+            search.progress.served_by = served_by;
+            search.progress.price = price;
+            (vec![search.progress()], false)
+        }
+        Some(SearchUpdate::Event(SearchEvent::Match(tx))) => {
+            let mut messages = vec![];
+            if search.progress.record_match(&tx) {
+                messages.push(search.progress());
+            }
+            messages.push(ServerMessage::Result {
+                search_id: search.id,
+                data: tx,
+            });
+            (messages, false)
+        }
+        Some(SearchUpdate::Event(event)) => {
+            let error = event.to_json()["error"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            (vec![ServerMessage::error(Some(search.id), error)], false)
+        }
+        Some(SearchUpdate::Failed(e)) => {
+            let retry_after = match e {
+                MevlogError::Busy { retry_after } => Some(retry_after),
+                _ => None,
+            };
+            let error = ServerMessage::Error {
+                search_id: Some(search.id),
+                error: e.to_string(),
+                errors: None,
+                retry_after,
+            };
+            (vec![error, search.done(DoneReason::Failed)], true)
+        }
+        Some(SearchUpdate::Finished(end)) => (vec![search.done(end.into())], true),
+        // The task ended without reporting, e.g. it panicked
+        None => (vec![search.done(DoneReason::Failed)], true),
     }
+}
 
-    if let Some(gas_price) = params.gas_price.clone() {
-        cmd.arg("--gas-price").arg(gas_price);
+/// Validates the params and spawns the search, replies with an error when
+/// they're invalid.
+async fn start_search(
+    sender: &mut SplitSink<WebSocket, Message>,
+    id: u64,
+    params: SearchParams,
+) -> Option<ActiveSearch> {
+    if let Err(errors) = params.validate() {
+        let error = ServerMessage::Error {
+            search_id: Some(id),
+            error: errors.to_string(),
+            errors: Some(errors),
+            retry_after: None,
+        };
+        let done = ServerMessage::Done {
+            search_id: id,
+            reason: DoneReason::Failed,
+            matches: 0,
+            elapsed_ms: 0,
+            served_by: None,
+            price: None,
+        };
+        if send_json(sender, &error).await {
+            send_json(sender, &done).await;
+        }
+        return None;
     }
 
-    cmd.env("RUST_LOG", "off");
+    let (cancel, cancel_rx) = oneshot::channel();
+    let (updates_tx, updates) = mpsc::channel(32);
+    let query = SearchQuery::streaming(&params);
+    tokio::spawn(RequestScope::current().run(run_search(
+        query,
+        params.rpc_url,
+        updates_tx,
+        cancel_rx,
+    )));
 
-    let (stdout_lines, stderr_lines) = cmd_output_stream(&mut cmd);
-
-    stream_output_lines(stdout_lines, stderr_lines, sender).await;
+    Some(ActiveSearch {
+        id,
+        cancel,
+        updates,
+        progress: SearchProgress::default(),
+    })
+}
 
-    tracing::info!("WebSocket connection closed");
+async fn cancel_search(sender: &mut SplitSink<WebSocket, Message>, search: ActiveSearch) -> bool {
+    let done = search.done(DoneReason::Cancelled);
+    let _ = search.cancel.send(());
+    send_json(sender, &done).await
 }
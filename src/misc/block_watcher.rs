@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::config::app_config::app_config;
+use crate::misc::{explore::explore_block, mevlog_cmd::MevlogError, rpc_utils::fetch_chain_head};
+
+/// Blocks at most processed in one poll when the head jumps ahead, older
+/// ones are skipped.
+const MAX_CATCH_UP: u64 = 5;
+const BLOCK_UPDATES_CAPACITY: usize = 16;
+
+/// Remembers the last seen head and hands out each new block number once.
+#[derive(Debug, Default)]
+pub struct BlockTail {
+    last: Option<u64>,
+}
+
+impl BlockTail {
+    /// Blocks that landed since the previous call, oldest first. Empty if the
+    /// head didn't move, or moved back because another RPC is behind.
+    pub fn advance(&mut self, head: u64) -> Vec<u64> {
+        let from = match self.last {
+            None => head,
+            Some(last) if head <= last => return vec![],
+            Some(last) => (last + 1).max(head.saturating_sub(MAX_CATCH_UP - 1)),
+        };
+        self.last = Some(head);
+        (from..=head).collect()
+    }
+}
+
+/// Explore data of one new block, shared by every subscriber of the chain.
+#[derive(Debug, Clone)]
+pub struct BlockUpdate {
+    pub chain_id: u64,
+    pub block_number: u64,
+    pub txs: Result<Value, MevlogError>,
+}
+
+type BlockSender = broadcast::Sender<Arc<BlockUpdate>>;
+
+static BLOCK_WATCHERS: LazyLock<Mutex<HashMap<u64, BlockSender>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn poll_interval() -> Duration {
+    Duration::from_millis(app_config().block_watcher.poll_ms)
+}
+
+/// Subscribes to new blocks of `chain_id`. The first subscriber starts the
+/// chain's watcher, it stops once the last receiver is dropped.
+pub fn subscribe(chain_id: u64) -> broadcast::Receiver<Arc<BlockUpdate>> {
+    let mut watchers = BLOCK_WATCHERS.lock().expect("block watchers lock poisoned");
+    if let Some(sender) = watchers.get(&chain_id) {
+        return sender.subscribe();
+    }
+
+    let (sender, receiver) = broadcast::channel(BLOCK_UPDATES_CAPACITY);
+    watchers.insert(chain_id, sender.clone());
+    tokio::spawn(watch_chain(chain_id, sender));
+    tracing::info!("Block watcher started for chain {}", chain_id);
+    receiver
+}
+
+/// Removes the watcher if nobody listens anymore. Checked under the same
+/// lock `subscribe` takes, so a new subscriber can't slip in between.
+fn stop_if_unused(chain_id: u64, sender: &BlockSender) -> bool {
+    let mut watchers = BLOCK_WATCHERS.lock().expect("block watchers lock poisoned");
+    if sender.receiver_count() > 0 {
+        return false;
+    }
+    watchers.remove(&chain_id);
+    tracing::info!("Block watcher stopped for chain {}", chain_id);
+    true
+}
+
+async fn watch_chain(chain_id: u64, sender: BlockSender) {
+    let interval = poll_interval();
+    let mut tail = BlockTail::default();
+
+    loop {
+        if stop_if_unused(chain_id, &sender) {
+            return;
+        }
+
+        let head = match fetch_chain_head(chain_id).await {
+            Ok(head) => head,
+            Err(e) => {
+                tracing::error!("Failed to get block number for chain {}: {}", chain_id, &e);
+                tokio::time::sleep(interval).await;
+                continue;
+            }
+        };
+
+        for block_number in tail.advance(head) {
+            let txs = explore_block(chain_id, Some(block_number.to_string())).await;
+            let update = BlockUpdate {
+                chain_id,
+                block_number,
+                txs,
+            };
+            // Fails only when every subscriber is gone, checked above
+            let _ = sender.send(Arc::new(update));
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_yields_each_block_once() {
+        let mut tail = BlockTail::default();
+
+        assert_eq!(tail.advance(100), vec![100]);
+        assert!(tail.advance(100).is_empty());
+        assert_eq!(tail.advance(103), vec![101, 102, 103]);
+        // A lagging RPC reports an older head
+        assert!(tail.advance(101).is_empty());
+        assert_eq!(tail.advance(104), vec![104]);
+    }
+
+    #[test]
+    fn tail_skips_blocks_beyond_catch_up() {
+        let mut tail = BlockTail::default();
+        tail.advance(100);
+
+        assert_eq!(tail.advance(200), vec![196, 197, 198, 199, 200]);
+    }
+}
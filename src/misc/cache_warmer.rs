@@ -0,0 +1,310 @@
+use std::time::{Duration, Instant};
+
+use eyre::{Result, eyre};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+
+use crate::misc::{
+    block_watcher::BlockTail,
+    mevlog_cmd::runner,
+    rpc_utils::{fetch_block_number, fetch_trusted_block_number, get_rpc_url},
+    scheduler_status::{record_warmer_error, record_warmer_head},
+    search_query::SearchQuery,
+    shutdown::shutdown_started,
+    utils::{measure_end, measure_start, uptime_ping},
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(4);
+const MIN_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(300);
+/// A warmer that ran this long before failing restarts without backoff.
+const HEALTHY_RUN: Duration = Duration::from_secs(600);
+/// Blocks between uptime pings of a chain.
+const UPTIME_PING_BLOCKS: u64 = 10;
+
+/// One chain kept warm in the mevlog cache by following its head, a
+/// `[[cache_warmer]]` table in the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WarmerConfig {
+    pub chain_id: u64,
+    /// Fixed RPC for the chain, one of its public RPCs is picked per block
+    /// when not set.
+    pub rpc_url: Option<String>,
+    #[serde(default = "default_poll_secs")]
+    pub poll_secs: u64,
+    pub uptime_url: Option<String>,
+}
+
+fn default_poll_secs() -> u64 {
+    DEFAULT_POLL_INTERVAL.as_secs()
+}
+
+impl WarmerConfig {
+    pub fn new(chain_id: u64) -> Self {
+        Self {
+            chain_id,
+            rpc_url: None,
+            poll_secs: default_poll_secs(),
+            uptime_url: None,
+        }
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_secs)
+    }
+
+    /// `CACHE_WARMER_CHAINS` (comma separated ids) replaces the warmed
+    /// chains, keeping the settings of those also in the config file. Each
+    /// chain's settings are overridden by `CACHE_WARMER_<ID>_RPC_URL`,
+    /// `CACHE_WARMER_<ID>_POLL_SECS` and `CACHE_WARMER_<ID>_UPTIME_URL`.
+    /// Mainnet still reads `REMOTE_ETH_RPC_URL` and
+    /// `UPTIME_URL_MAINNET_CACHE` when its own variables are not set.
+    pub fn apply_env_overrides(
+        warmers: &mut Vec<Self>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
+        if let Some(chains) = lookup("CACHE_WARMER_CHAINS") {
+            let configured = std::mem::take(warmers);
+            *warmers = chains
+                .split(',')
+                .map(str::trim)
+                .filter(|chain_id| !chain_id.is_empty())
+                .map(|chain_id| {
+                    let chain_id: u64 = chain_id.parse().map_err(|_| {
+                        eyre!("Invalid chain id '{chain_id}' in CACHE_WARMER_CHAINS")
+                    })?;
+                    Ok(configured
+                        .iter()
+                        .find(|warmer| warmer.chain_id == chain_id)
+                        .cloned()
+                        .unwrap_or_else(|| Self::new(chain_id)))
+                })
+                .collect::<Result<_>>()?;
+        }
+
+        for warmer in warmers.iter_mut() {
+            let chain_id = warmer.chain_id;
+            let var = |name: &str| lookup(&format!("CACHE_WARMER_{chain_id}_{name}"));
+            let legacy = |name: &str| if chain_id == 1 { lookup(name) } else { None };
+
+            if let Some(secs) = var("POLL_SECS") {
+                warmer.poll_secs = secs
+                    .trim()
+                    .parse()
+                    .map_err(|_| eyre!("Invalid CACHE_WARMER_{chain_id}_POLL_SECS '{secs}'"))?;
+            }
+            if let Some(rpc_url) = var("RPC_URL").or_else(|| legacy("REMOTE_ETH_RPC_URL")) {
+                warmer.rpc_url = Some(rpc_url);
+            }
+            if let Some(uptime_url) =
+                var("UPTIME_URL").or_else(|| legacy("UPTIME_URL_MAINNET_CACHE"))
+            {
+                warmer.uptime_url = Some(uptime_url);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the chain's warmer until shutdown, restarting it with a growing
+/// backoff whenever it errors or panics so other chains keep going.
+pub async fn supervise(config: WarmerConfig) {
+    let mut backoff = MIN_RESTART_BACKOFF;
+
+    loop {
+        let started_at = Instant::now();
+        // Dropping a run in progress kills its mevlog process
+        let result = tokio::select! {
+            result = std::panic::AssertUnwindSafe(warm_chain(&config)).catch_unwind() => result,
+            _ = shutdown_started() => break,
+        };
+
+        match result {
+            Ok(Ok(_)) => tracing::error!("Cache warmer for chain {} stopped", config.chain_id),
+            Ok(Err(e)) => {
+                tracing::error!(
+                    "Cache warmer for chain {} errored: {:?}",
+                    config.chain_id,
+                    e
+                );
+                record_warmer_error(config.chain_id, e.to_string()).await;
+            }
+            Err(e) => tracing::error!(
+                "Cache warmer for chain {} panicked: {:?}",
+                config.chain_id,
+                e
+            ),
+        }
+
+        backoff = next_backoff(backoff, started_at.elapsed());
+        tracing::info!(
+            "Restarting cache warmer for chain {} in {:?}",
+            config.chain_id,
+            backoff
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown_started() => break,
+        }
+    }
+
+    tracing::info!("Cache warmer for chain {} stopped", config.chain_id);
+}
+
+fn next_backoff(previous: Duration, ran_for: Duration) -> Duration {
+    if ran_for >= HEALTHY_RUN {
+        MIN_RESTART_BACKOFF
+    } else {
+        (previous * 2).min(MAX_RESTART_BACKOFF)
+    }
+}
+
+async fn warm_chain(config: &WarmerConfig) -> Result<()> {
+    let chain_id = config.chain_id;
+    let mut tail = BlockTail::default();
+    tracing::info!("Cache warmer started for chain {}", chain_id);
+
+    loop {
+        let rpc_url = match &config.rpc_url {
+            Some(rpc_url) => rpc_url.clone(),
+            None => get_rpc_url(chain_id)
+                .await?
+                .ok_or_else(|| eyre!("No RPC URL for chain {chain_id}"))?,
+        };
+
+        let head = match &config.rpc_url {
+            Some(_) => fetch_trusted_block_number(&rpc_url).await,
+            None => fetch_block_number(&rpc_url).await,
+        };
+        let head = match head {
+            Ok(block_number) => block_number,
+            Err(e) => {
+                tracing::error!("Failed to get block number for chain {}: {}", chain_id, &e);
+                tokio::time::sleep(config.poll_interval()).await;
+                continue;
+            }
+        };
+
+        // Only the newest block is warmed, `latest` covers it
+        let Some(new_block_number) = tail.advance(head).last().copied() else {
+            tracing::debug!("No new blocks for chain {}, sleeping: {}", chain_id, head);
+            tokio::time::sleep(config.poll_interval()).await;
+            continue;
+        };
+
+        let query = SearchQuery {
+            rpc_url: Some(rpc_url),
+            trusted_rpc: config.rpc_url.is_some(),
+            ..SearchQuery::new(chain_id, "latest")
+        };
+
+        let start = measure_start(&format!("mevlog latest chain {chain_id}"));
+        if let Err(e) = runner().output(&query.to_cmd()).await {
+            tracing::error!(
+                "Failed to run mevlog search latest for chain {}: {}",
+                chain_id,
+                &e
+            );
+            record_warmer_error(chain_id, e.to_string()).await;
+            continue;
+        }
+        measure_end(start);
+        record_warmer_head(chain_id, new_block_number).await;
+
+        if new_block_number % UPTIME_PING_BLOCKS == 0
+            && let Some(uptime_url) = &config.uptime_url
+        {
+            tracing::info!("Cache warmer uptime ping for chain {}", chain_id);
+            if let Err(e) = uptime_ping(uptime_url).await {
+                tracing::error!("Failed to uptime ping: {}", &e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn defaults_to_mainnet_with_legacy_vars() -> Result<()> {
+        let mut warmers = vec![WarmerConfig::new(1)];
+        WarmerConfig::apply_env_overrides(
+            &mut warmers,
+            lookup(&[
+                ("REMOTE_ETH_RPC_URL", "http://eth"),
+                ("UPTIME_URL_MAINNET_CACHE", "http://uptime"),
+            ]),
+        )?;
+
+        assert_eq!(
+            warmers,
+            vec![WarmerConfig {
+                chain_id: 1,
+                rpc_url: Some("http://eth".to_string()),
+                poll_secs: DEFAULT_POLL_INTERVAL.as_secs(),
+                uptime_url: Some("http://uptime".to_string()),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reads_per_chain_settings() -> Result<()> {
+        let mut warmers = vec![WarmerConfig {
+            poll_secs: 12,
+            ..WarmerConfig::new(56)
+        }];
+        WarmerConfig::apply_env_overrides(
+            &mut warmers,
+            lookup(&[
+                ("CACHE_WARMER_CHAINS", "1, 56"),
+                ("REMOTE_ETH_RPC_URL", "http://eth"),
+                ("CACHE_WARMER_56_UPTIME_URL", "http://uptime-bsc"),
+            ]),
+        )?;
+
+        assert_eq!(warmers.len(), 2);
+        assert_eq!(warmers[0].rpc_url.as_deref(), Some("http://eth"));
+        assert_eq!(warmers[1].chain_id, 56);
+        assert_eq!(warmers[1].rpc_url, None);
+        assert_eq!(warmers[1].poll_interval(), Duration::from_secs(12));
+        assert_eq!(warmers[1].uptime_url.as_deref(), Some("http://uptime-bsc"));
+
+        let invalid = WarmerConfig::apply_env_overrides(
+            &mut warmers,
+            lookup(&[("CACHE_WARMER_CHAINS", "1,bsc")]),
+        );
+        assert!(invalid.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn backoff_grows_and_resets_after_healthy_run() {
+        let short = Duration::from_secs(1);
+
+        assert_eq!(
+            next_backoff(Duration::from_secs(1), short),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            next_backoff(Duration::from_secs(200), short),
+            MAX_RESTART_BACKOFF
+        );
+        assert_eq!(
+            next_backoff(Duration::from_secs(200), HEALTHY_RUN),
+            MIN_RESTART_BACKOFF
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Instant;
+
+use serde_json::Value;
+
+use crate::misc::{
+    explore_cache::{CachedBlock, EXPLORE_CACHE, ExploreKey, FINALITY_DEPTH},
+    mevlog_cmd::{MevlogError, run_json_first_line},
+    price_history::{PriceQuote, price_for_block, price_for_blocks},
+    rpc_utils::{
+        FAILOVER_DEADLINE, MAX_RPC_ATTEMPTS, ServedBy, fetch_chain_head, get_next_rpc_url,
+        resolve_rpc_url,
+    },
+    search_query::{SearchFormat, SearchQuery},
+    utils::{measure_end, measure_start},
+};
+
+/// Every transaction of a single block, as shown on the explore page.
+#[hotpath::measure]
+pub async fn explore_block(
+    chain_id: u64,
+    block_number: Option<String>,
+) -> Result<Value, MevlogError> {
+    let query = SearchQuery {
+        format: Some(SearchFormat::Json),
+        rpc_timeout_ms: Some(500),
+        latest_offset: Some(1), // Improves caching
+        ..SearchQuery::new(
+            chain_id,
+            block_number.unwrap_or_else(|| "latest".to_string()),
+        )
+    };
+
+    run_block_query(query).await.map(|output| output.txs)
+}
+
+/// Serialized explore data of a single block, served from [`EXPLORE_CACHE`]
+/// when possible. `latest` is pinned to a concrete block first, so it's
+/// cached like any other recent block. Blocks read through a user-supplied
+/// `rpc_url` bypass the cache, so they can't end up in other users' results.
+#[hotpath::measure]
+pub async fn explore_block_cached(
+    chain_id: u64,
+    block_number: Option<String>,
+    rpc_url: Option<String>,
+) -> Result<CachedBlock, MevlogError> {
+    if let Some(rpc_url) = rpc_url {
+        let rpc_url = resolve_rpc_url(chain_id, Some(&rpc_url)).await?;
+        return explore_uncached(chain_id, block_number, rpc_url).await;
+    }
+
+    let (block_number, head) = match block_number.as_deref() {
+        None | Some("latest") => match fetch_chain_head(chain_id).await {
+            Ok(head) => (head.saturating_sub(1), Some(head)),
+            Err(e) => {
+                tracing::warn!(
+                    "Explore cache skipped, no head for chain {}: {}",
+                    chain_id,
+                    &e
+                );
+                return explore_uncached(chain_id, block_number, None).await;
+            }
+        },
+        Some(number) => match number.parse::<u64>() {
+            Ok(number) => (number, None),
+            Err(_) => return explore_uncached(chain_id, block_number, None).await,
+        },
+    };
+
+    // The head is only known when `latest` was asked for
+    let price = match head {
+        Some(_) => price_for_block(chain_id, None).await,
+        None => price_for_block(chain_id, Some(block_number)).await,
+    };
+
+    let key = ExploreKey::new(chain_id, block_number, price.map(|price| price.usd));
+    // Served with the price its USD values were computed with, which may
+    // differ from the current one within the same bucket
+    if let Some(cached) = EXPLORE_CACHE.get(&key).await {
+        return Ok(cached);
+    }
+
+    let head = match head {
+        Some(head) => Some(head),
+        None => fetch_chain_head(chain_id).await.ok(),
+    };
+    let finalized = head.is_some_and(|head| head >= block_number + FINALITY_DEPTH);
+
+    let query = SearchQuery {
+        format: Some(SearchFormat::Json),
+        rpc_timeout_ms: Some(500),
+        native_token_price: price.map(|price| price.usd),
+        ..SearchQuery::new(chain_id, block_number.to_string())
+    };
+    let output = run_block_query(query).await?;
+    let block = CachedBlock::new(output.txs.to_string(), finalized)
+        .served_by(output.served_by)
+        .priced(price.or(output.price));
+    EXPLORE_CACHE.insert(key, block.clone()).await;
+    Ok(block)
+}
+
+async fn explore_uncached(
+    chain_id: u64,
+    block_number: Option<String>,
+    rpc_url: Option<String>,
+) -> Result<CachedBlock, MevlogError> {
+    let query = SearchQuery {
+        format: Some(SearchFormat::Json),
+        rpc_timeout_ms: Some(500),
+        latest_offset: Some(1),
+        rpc_url,
+        ..SearchQuery::new(
+            chain_id,
+            block_number.unwrap_or_else(|| "latest".to_string()),
+        )
+    };
+    let output = run_block_query(query).await?;
+    Ok(CachedBlock::new(output.txs.to_string(), false)
+        .served_by(output.served_by)
+        .priced(output.price))
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockQueryOutput {
+    pub txs: Value,
+    /// The RPC that returned `txs`, `None` when mevlog picked one itself.
+    pub served_by: Option<ServedBy>,
+    /// The native token price filled in, `None` when the query came with
+    /// its own or none was known.
+    pub price: Option<PriceQuote>,
+}
+
+/// Runs a single-block query with one of the chain's RPC URLs, unless the
+/// query brings its own, and the native token price at the block's time,
+/// unless already set, filled in. When a public RPC fails the query is
+/// retried against the next best one, up to [`MAX_RPC_ATTEMPTS`] times
+/// within [`FAILOVER_DEADLINE`].
+pub async fn run_block_query(mut query: SearchQuery) -> Result<BlockQueryOutput, MevlogError> {
+    let price = match query.native_token_price {
+        Some(_) => None,
+        None => price_for_blocks(query.chain_id, &query.blocks).await,
+    };
+    if let Some(price) = price {
+        query.native_token_price = Some(price.usd);
+    }
+
+    // A user-supplied RPC is used as is, without failing over
+    let custom_rpc_url = query.rpc_url.take();
+    let deadline = Instant::now() + FAILOVER_DEADLINE;
+    let mut tried: Vec<String> = vec![];
+
+    loop {
+        query.rpc_url = match &custom_rpc_url {
+            Some(rpc_url) => Some(rpc_url.clone()),
+            None => get_next_rpc_url(query.chain_id, &tried)
+                .await
+                .ok()
+                .flatten(),
+        };
+
+        let cmd = query.to_cmd();
+        tracing::debug!("explore command: {:?}", cmd.args());
+
+        let start = measure_start("explore cmd");
+        let result = run_json_first_line::<Value>(&cmd).await;
+
+        let Some(rpc_url) = query.rpc_url.take() else {
+            return result.map(|txs| BlockQueryOutput {
+                txs,
+                served_by: None,
+                price,
+            });
+        };
+        tried.push(rpc_url.clone());
+
+        match result {
+            Ok(txs) => {
+                measure_end(start);
+                return Ok(BlockQueryOutput {
+                    txs,
+                    served_by: Some(ServedBy {
+                        rpc_url,
+                        attempts: tried.len() as u32,
+                    }),
+                    price,
+                });
+            }
+            Err(e)
+                if e.is_rpc_failure()
+                    && custom_rpc_url.is_none()
+                    && tried.len() < MAX_RPC_ATTEMPTS as usize
+                    && Instant::now() < deadline
+                    && get_next_rpc_url(query.chain_id, &tried)
+                        .await
+                        .is_ok_and(|next| next.is_some()) =>
+            {
+                tracing::warn!(
+                    "Explore on chain {} failed with {}, retrying: {}",
+                    query.chain_id,
+                    rpc_url,
+                    &e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
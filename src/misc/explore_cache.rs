@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::config::app_config::app_config;
+use crate::misc::{price_history::PriceQuote, rpc_utils::ServedBy};
+
+pub static EXPLORE_CACHE: LazyLock<ExploreCache> = LazyLock::new(|| {
+    let config = &app_config().explore_cache;
+    ExploreCache::new(config.capacity, config.dir.as_ref().map(PathBuf::from))
+});
+
+/// Blocks this far behind the head are treated as final and cached for good.
+pub const FINALITY_DEPTH: u64 = 64;
+/// How long a block that can still be reorged out stays cached.
+pub const RECENT_BLOCK_TTL: Duration = Duration::from_secs(5);
+/// Native token prices within the same bucket share cached results.
+const PRICE_BUCKET_USD: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExploreKey {
+    pub chain_id: u64,
+    pub block_number: u64,
+    pub price_bucket: Option<u64>,
+}
+
+impl ExploreKey {
+    pub fn new(chain_id: u64, block_number: u64, price: Option<f64>) -> Self {
+        Self {
+            chain_id,
+            block_number,
+            price_bucket: price.map(|price| (price / PRICE_BUCKET_USD).round() as u64),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match self.price_bucket {
+            Some(bucket) => format!("{}-{}.json", self.block_number, bucket),
+            None => format!("{}.json", self.block_number),
+        }
+    }
+}
+
+/// A serialized explore response, shared by every request for the same key.
+#[derive(Debug, Clone)]
+pub struct CachedBlock {
+    pub body: Arc<str>,
+    pub etag: String,
+    pub finalized: bool,
+    /// Not kept on disk, blocks read back from there have none.
+    pub served_by: Option<ServedBy>,
+    /// Native token price the USD values were computed with.
+    pub price: Option<PriceQuote>,
+}
+
+impl CachedBlock {
+    pub fn new(body: String, finalized: bool) -> Self {
+        let etag = format!("\"{:016x}\"", fnv1a(body.as_bytes()));
+        Self {
+            body: body.into(),
+            etag,
+            finalized,
+            served_by: None,
+            price: None,
+        }
+    }
+
+    pub fn served_by(mut self, served_by: Option<ServedBy>) -> Self {
+        self.served_by = served_by;
+        self
+    }
+
+    pub fn priced(mut self, price: Option<PriceQuote>) -> Self {
+        self.price = price;
+        self
+    }
+
+    pub fn cache_control(&self) -> String {
+        if self.finalized {
+            "public, max-age=31536000, immutable".to_string()
+        } else {
+            format!("public, max-age={}", RECENT_BLOCK_TTL.as_secs())
+        }
+    }
+}
+
+/// Stable across restarts unlike `DefaultHasher`, so ETags of blocks read
+/// back from disk keep matching what browsers have.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+struct Entry {
+    block: CachedBlock,
+    expires_at: Option<Instant>,
+    last_used: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExploreCacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+}
+
+/// Bounded LRU of explore results keyed by chain, block and price bucket.
+/// Final blocks are also written to `dir` when set, so they survive
+/// restarts and evictions.
+pub struct ExploreCache {
+    capacity: usize,
+    dir: Option<PathBuf>,
+    entries: Mutex<HashMap<ExploreKey, Entry>>,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ExploreCache {
+    pub fn new(capacity: usize, dir: Option<PathBuf>) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            dir,
+            entries: Mutex::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            disk_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn get(&self, key: &ExploreKey) -> Option<CachedBlock> {
+        if let Some(block) = self.get_memory(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(block);
+        }
+
+        if let Some(block) = self.read_disk(key).await {
+            self.disk_hits.fetch_add(1, Ordering::Relaxed);
+            self.insert_memory(*key, block.clone());
+            return Some(block);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    pub async fn insert(&self, key: ExploreKey, block: CachedBlock) {
+        if block.finalized {
+            self.write_disk(&key, &block).await;
+        }
+        self.insert_memory(key, block);
+    }
+
+    pub fn stats(&self) -> ExploreCacheStats {
+        ExploreCacheStats {
+            entries: self
+                .entries
+                .lock()
+                .expect("explore cache lock poisoned")
+                .len(),
+            capacity: self.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+            disk_hits: self.disk_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn get_memory(&self, key: &ExploreKey) -> Option<CachedBlock> {
+        let mut entries = self.entries.lock().expect("explore cache lock poisoned");
+        let entry = entries.get_mut(key)?;
+        if entry
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Instant::now())
+        {
+            entries.remove(key);
+            return None;
+        }
+        entry.last_used = self.tick();
+        Some(entry.block.clone())
+    }
+
+    fn insert_memory(&self, key: ExploreKey, block: CachedBlock) {
+        let expires_at = (!block.finalized).then(|| Instant::now() + RECENT_BLOCK_TTL);
+        let mut entries = self.entries.lock().expect("explore cache lock poisoned");
+
+        if !entries.contains_key(&key) && entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                block,
+                expires_at,
+                last_used: self.tick(),
+            },
+        );
+    }
+
+    fn disk_path(&self, key: &ExploreKey) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(key.chain_id.to_string()).join(key.file_name()))
+    }
+
+    async fn read_disk(&self, key: &ExploreKey) -> Option<CachedBlock> {
+        let path = self.disk_path(key)?;
+        let body = tokio::fs::read_to_string(&path).await.ok()?;
+        let price = tokio::fs::read(price_path(&path))
+            .await
+            .ok()
+            .and_then(|price| serde_json::from_slice(&price).ok());
+        Some(CachedBlock::new(body, true).priced(price))
+    }
+
+    async fn write_disk(&self, key: &ExploreKey, block: &CachedBlock) {
+        let Some(path) = self.disk_path(key) else {
+            return;
+        };
+        let result = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            if let Some(price) = &block.price {
+                tokio::fs::write(price_path(&path), serde_json::to_vec(price)?).await?;
+            }
+            tokio::fs::write(&path, block.body.as_bytes()).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!("Failed to write explore cache {}: {}", path.display(), &e);
+        }
+    }
+}
+
+/// The price a block on disk was computed with, kept next to it.
+fn price_path(path: &Path) -> PathBuf {
+    path.with_extension("price.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block_number: u64, finalized: bool) -> CachedBlock {
+        CachedBlock::new(format!("[{block_number}]"), finalized)
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let cache = ExploreCache::new(2, None);
+        let (a, b, c) = (
+            ExploreKey::new(1, 100, None),
+            ExploreKey::new(1, 101, None),
+            ExploreKey::new(1, 102, None),
+        );
+
+        cache.insert(a, block(100, true)).await;
+        cache.insert(b, block(101, true)).await;
+        assert!(cache.get(&a).await.is_some());
+        cache.insert(c, block(102, true)).await;
+
+        assert!(cache.get(&a).await.is_some());
+        assert!(cache.get(&b).await.is_none());
+        assert!(cache.get(&c).await.is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (3, 1, 2));
+    }
+
+    #[tokio::test]
+    async fn keeps_final_blocks_on_disk() -> std::io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("explore-cache-{}", uuid::Uuid::new_v4()));
+        let key = ExploreKey::new(10, 200, Some(2512.0));
+        let recent_key = ExploreKey::new(10, 201, Some(2512.0));
+
+        let price = PriceQuote {
+            usd: 2509.5,
+            timestamp: 1_700_000_000,
+            historical: true,
+            age_secs: None,
+            stale: false,
+        };
+        let cache = ExploreCache::new(10, Some(dir.clone()));
+        cache
+            .insert(key, block(200, true).priced(Some(price)))
+            .await;
+        cache.insert(recent_key, block(201, false)).await;
+
+        let restarted = ExploreCache::new(10, Some(dir.clone()));
+        let cached = restarted
+            .get(&key)
+            .await
+            .expect("final block read from disk");
+        assert_eq!(cached.etag, block(200, true).etag);
+        assert_eq!(cached.price, Some(price));
+        assert!(restarted.get(&recent_key).await.is_none());
+        assert_eq!(restarted.stats().disk_hits, 1);
+
+        std::fs::remove_dir_all(dir)
+    }
+
+    #[test]
+    fn prices_share_buckets() {
+        assert_eq!(
+            ExploreKey::new(1, 1, Some(2511.0)),
+            ExploreKey::new(1, 1, Some(2514.9))
+        );
+        assert_ne!(
+            ExploreKey::new(1, 1, Some(2511.0)),
+            ExploreKey::new(1, 1, Some(2530.0))
+        );
+        assert_ne!(
+            ExploreKey::new(1, 1, None),
+            ExploreKey::new(1, 1, Some(0.0))
+        );
+    }
+}
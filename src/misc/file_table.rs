@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+
+/// How often the file's modification time is checked.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Loaded<T> {
+    modified: Option<SystemTime>,
+    checked_at: Instant,
+    value: Option<Arc<T>>,
+}
+
+/// A JSON file the scheduler replaces atomically, served from memory to the
+/// server. The file is checked at most every [`RECHECK_INTERVAL`] and
+/// re-read when it changed, without blocking the runtime thread.
+pub struct FileTable<T> {
+    path: fn() -> PathBuf,
+    loaded: Mutex<Option<Loaded<T>>>,
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> FileTable<T> {
+    pub const fn new(path: fn() -> PathBuf) -> Self {
+        Self {
+            path,
+            loaded: Mutex::const_new(None),
+        }
+    }
+
+    /// The file's latest contents, `None` while it's missing or invalid.
+    pub async fn get(&self) -> Option<Arc<T>> {
+        // Held while reloading, so concurrent lookups wait for one read
+        let mut loaded = self.loaded.lock().await;
+        if let Some(loaded) = loaded.as_ref()
+            && loaded.checked_at.elapsed() < RECHECK_INTERVAL
+        {
+            return loaded.value.clone();
+        }
+
+        let path = (self.path)();
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let value = match loaded.as_ref() {
+            Some(previous) if modified.is_some() && previous.modified == modified => {
+                previous.value.clone()
+            }
+            _ if modified.is_some() => read(path).await,
+            _ => None,
+        };
+
+        *loaded = Some(Loaded {
+            modified,
+            checked_at: Instant::now(),
+            value: value.clone(),
+        });
+        value
+    }
+}
+
+async fn read<T: DeserializeOwned + Send + 'static>(path: PathBuf) -> Option<Arc<T>> {
+    let contents = tokio::fs::read(&path).await.ok()?;
+    let parsed = tokio::task::spawn_blocking(move || serde_json::from_slice::<T>(&contents))
+        .await
+        .ok()?;
+    match parsed {
+        Ok(value) => Some(Arc::new(value)),
+        Err(e) => {
+            tracing::error!("Failed to parse {}: {}", path.display(), &e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn test_path() -> PathBuf {
+        std::env::temp_dir().join(format!("file-table-{}.json", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn reads_the_file_once_it_exists() {
+        let _ = std::fs::remove_file(test_path());
+        let table: FileTable<HashMap<String, u64>> = FileTable::new(test_path);
+        assert!(table.get().await.is_none());
+
+        std::fs::write(test_path(), r#"{"a": 1}"#).unwrap();
+        *table.loaded.lock().await = None;
+        assert_eq!(table.get().await.unwrap()["a"], 1);
+
+        // Served from memory until the next check
+        std::fs::remove_file(test_path()).unwrap();
+        assert_eq!(table.get().await.unwrap()["a"], 1);
+    }
+}
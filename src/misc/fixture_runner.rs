@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use eyre::Result;
+use futures::{FutureExt, StreamExt, future::BoxFuture, stream};
+use serde_json::Value;
+
+use crate::misc::mevlog_cmd::{MevlogCmd, MevlogError, MevlogRunner, MevlogStream};
+
+/// Replays recorded mevlog output instead of spawning the binary, so every
+/// route can be exercised offline.
+///
+/// `search` replies with `output.json` and `chain-info` with
+/// `chain-output.json`, shaped the way the requested `--format` would print
+/// them.
+pub struct FixtureRunner {
+    search: Vec<Value>,
+    chain_info: Value,
+}
+
+impl FixtureRunner {
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let search = read_json(&dir.join("output.json"))?;
+        let chain_info = read_json(&dir.join("chain-output.json"))?;
+
+        Ok(Self { search, chain_info })
+    }
+
+    fn reply(&self, cmd: &MevlogCmd) -> Result<Vec<String>, MevlogError> {
+        match cmd.subcommand() {
+            "search" => {
+                if cmd.flag_value("--format") == Some("json-stream") {
+                    Ok(self.search.iter().map(Value::to_string).collect())
+                } else {
+                    Ok(vec![Value::from(self.search.clone()).to_string()])
+                }
+            }
+            "chain-info" => {
+                let mut chain_info = self.chain_info.clone();
+                if let Some(chain_id) = cmd
+                    .flag_value("--chain-id")
+                    .and_then(|id| id.parse::<u64>().ok())
+                {
+                    chain_info["chain_id"] = Value::from(chain_id);
+                }
+                if cmd.has_flag("--skip-urls")
+                    && let Some(obj) = chain_info.as_object_mut()
+                {
+                    obj.remove("rpc_urls");
+                }
+                Ok(vec![chain_info.to_string()])
+            }
+            other => Err(MevlogError::Failed(format!(
+                "No fixture recorded for `mevlog {other}`"
+            ))),
+        }
+    }
+}
+
+impl MevlogRunner for FixtureRunner {
+    fn output(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>> {
+        let reply = self.reply(cmd).map(|lines| lines.join("\n"));
+        async move { reply }.boxed()
+    }
+
+    fn first_line(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>> {
+        let reply = self
+            .reply(cmd)
+            .and_then(|lines| lines.into_iter().next().ok_or(MevlogError::NoOutput));
+        async move { reply }.boxed()
+    }
+
+    fn stream(&self, cmd: &MevlogCmd) -> Result<MevlogStream, MevlogError> {
+        let lines = self.reply(cmd)?;
+
+        Ok(MevlogStream::new(
+            stream::iter(lines.into_iter().map(Ok)).boxed(),
+            stream::empty().boxed(),
+        ))
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Upper bounds of the duration histograms, in seconds.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct MevlogRuns {
+    failed: u64,
+    duration: Histogram,
+}
+
+/// Counters the server keeps in memory, exported in the Prometheus text
+/// format by `/metrics`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Keyed by route, method and status.
+    requests: Mutex<BTreeMap<(String, String, u16), Histogram>>,
+    active_websockets: AtomicI64,
+    /// Keyed by subcommand and chain.
+    mevlog_runs: Mutex<BTreeMap<(String, String), MevlogRuns>>,
+    /// Keyed by cache name and whether it was a hit.
+    cache_lookups: Mutex<BTreeMap<(&'static str, bool), u64>>,
+}
+
+impl Metrics {
+    pub fn record_request(&self, route: &str, method: &str, status: u16, duration: Duration) {
+        self.requests
+            .lock()
+            .expect("metrics lock poisoned")
+            .entry((route.to_string(), method.to_string(), status))
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Counts the socket as active until the guard is dropped.
+    pub fn websocket_opened(&self) -> WebSocketGuard<'_> {
+        self.active_websockets.fetch_add(1, Ordering::Relaxed);
+        WebSocketGuard(self)
+    }
+
+    /// Records a finished mevlog run, `chain_id` is `None` for commands
+    /// that don't query a chain.
+    pub fn record_mevlog_run(
+        &self,
+        command: &str,
+        chain_id: Option<u64>,
+        duration: Duration,
+        failed: bool,
+    ) {
+        let chain = chain_id.map_or_else(|| "none".to_string(), |id| id.to_string());
+        let mut runs = self.mevlog_runs.lock().expect("metrics lock poisoned");
+        let runs = runs.entry((command.to_string(), chain)).or_default();
+        runs.duration.observe(duration);
+        if failed {
+            runs.failed += 1;
+        }
+    }
+
+    pub fn record_cache_lookup(&self, cache: &'static str, hit: bool) {
+        *self
+            .cache_lookups
+            .lock()
+            .expect("metrics lock poisoned")
+            .entry((cache, hit))
+            .or_default() += 1;
+    }
+
+    /// Writes everything recorded so far.
+    pub fn write_to(&self, out: &mut MetricsText) {
+        let requests = self.requests.lock().expect("metrics lock poisoned").clone();
+        out.header(
+            "http_requests_total",
+            "counter",
+            "HTTP requests by route, method and status.",
+        );
+        for ((route, method, status), histogram) in &requests {
+            let status = status.to_string();
+            out.sample(
+                "http_requests_total",
+                &[("route", route), ("method", method), ("status", &status)],
+                histogram.count,
+            );
+        }
+        out.header(
+            "http_request_duration_seconds",
+            "histogram",
+            "Time to respond to HTTP requests.",
+        );
+        for ((route, method, status), histogram) in &requests {
+            let status = status.to_string();
+            out.histogram(
+                "http_request_duration_seconds",
+                &[("route", route), ("method", method), ("status", &status)],
+                histogram,
+            );
+        }
+
+        out.header(
+            "websockets_active",
+            "gauge",
+            "WebSocket connections currently open.",
+        );
+        out.sample(
+            "websockets_active",
+            &[],
+            self.active_websockets.load(Ordering::Relaxed),
+        );
+
+        let mevlog_runs = self
+            .mevlog_runs
+            .lock()
+            .expect("metrics lock poisoned")
+            .clone();
+        out.header(
+            "mevlog_runs_total",
+            "counter",
+            "mevlog subprocesses spawned, by subcommand and chain.",
+        );
+        for ((command, chain), runs) in &mevlog_runs {
+            out.sample(
+                "mevlog_runs_total",
+                &[("command", command), ("chain_id", chain)],
+                runs.duration.count,
+            );
+        }
+        out.header(
+            "mevlog_run_failures_total",
+            "counter",
+            "mevlog runs that failed or timed out, by subcommand and chain.",
+        );
+        for ((command, chain), runs) in &mevlog_runs {
+            out.sample(
+                "mevlog_run_failures_total",
+                &[("command", command), ("chain_id", chain)],
+                runs.failed,
+            );
+        }
+        out.header(
+            "mevlog_run_duration_seconds",
+            "histogram",
+            "How long mevlog subprocesses ran.",
+        );
+        for ((command, chain), runs) in &mevlog_runs {
+            out.histogram(
+                "mevlog_run_duration_seconds",
+                &[("command", command), ("chain_id", chain)],
+                &runs.duration,
+            );
+        }
+
+        let cache_lookups = self
+            .cache_lookups
+            .lock()
+            .expect("metrics lock poisoned")
+            .clone();
+        out.header(
+            "cache_lookups_total",
+            "counter",
+            "In-memory cache lookups by cache and result.",
+        );
+        for ((cache, hit), count) in &cache_lookups {
+            let result = if *hit { "hit" } else { "miss" };
+            out.sample(
+                "cache_lookups_total",
+                &[("cache", cache), ("result", result)],
+                count,
+            );
+        }
+    }
+}
+
+pub struct WebSocketGuard<'a>(&'a Metrics);
+
+impl Drop for WebSocketGuard<'_> {
+    fn drop(&mut self) {
+        self.0.active_websockets.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A Prometheus text format exposition being built.
+#[derive(Debug, Default)]
+pub struct MetricsText(String);
+
+impl MetricsText {
+    pub fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let _ = writeln!(self.0, "{name}{} {value}", format_labels(labels));
+    }
+
+    fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let bound = bound.to_string();
+            let labels = [labels, &[("le", bound.as_str())]].concat();
+            self.sample(&format!("{name}_bucket"), &labels, cumulative);
+        }
+        let labels_inf = [labels, &[("le", "+Inf")]].concat();
+        self.sample(&format!("{name}_bucket"), &labels_inf, histogram.count);
+        self.sample(&format!("{name}_sum"), labels, histogram.sum);
+        self.sample(&format!("{name}_count"), labels, histogram.count);
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_prometheus_text() {
+        let metrics = Metrics::default();
+        metrics.record_request("/api/search", "GET", 200, Duration::from_millis(30));
+        metrics.record_request("/api/search", "GET", 200, Duration::from_secs(60));
+        metrics.record_mevlog_run("search", Some(1), Duration::from_secs(2), true);
+        metrics.record_cache_lookup("rpc_urls", false);
+        let guard = metrics.websocket_opened();
+
+        let mut out = MetricsText::default();
+        metrics.write_to(&mut out);
+        let text = out.into_string();
+
+        for expected in [
+            "# TYPE http_requests_total counter",
+            r#"http_requests_total{route="/api/search",method="GET",status="200"} 2"#,
+            r#"http_request_duration_seconds_bucket{route="/api/search",method="GET",status="200",le="0.05"} 1"#,
+            r#"http_request_duration_seconds_bucket{route="/api/search",method="GET",status="200",le="30"} 1"#,
+            r#"http_request_duration_seconds_bucket{route="/api/search",method="GET",status="200",le="+Inf"} 2"#,
+            r#"http_request_duration_seconds_count{route="/api/search",method="GET",status="200"} 2"#,
+            "websockets_active 1",
+            r#"mevlog_runs_total{command="search",chain_id="1"} 1"#,
+            r#"mevlog_run_failures_total{command="search",chain_id="1"} 1"#,
+            r#"cache_lookups_total{cache="rpc_urls",result="miss"} 1"#,
+        ] {
+            assert!(text.contains(expected), "{expected} missing in:\n{text}");
+        }
+
+        drop(guard);
+        let mut out = MetricsText::default();
+        metrics.write_to(&mut out);
+        assert!(out.into_string().contains("websockets_active 0"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(
+            format_labels(&[("route", "a\"b\\c\nd")]),
+            r#"{route="a\"b\\c\nd"}"#
+        );
+        assert_eq!(format_labels(&[]), "");
+    }
+}
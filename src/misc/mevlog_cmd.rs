@@ -0,0 +1,712 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use futures::{FutureExt, StreamExt, future::BoxFuture, stream::BoxStream};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::Notify;
+use tokio::time::timeout;
+use tokio_stream::wrappers::LinesStream;
+
+use crate::config::app_config::app_config;
+use crate::controllers::base_controller::{DATA_FETCH_ERROR, decorate_error_message};
+use crate::misc::custom_rpc::RPC_POLICY;
+use crate::misc::metrics::METRICS;
+use crate::misc::mevlog_lib::LibraryRunner;
+use crate::misc::mevlog_pool::{MEVLOG_POOL, PoolPermit};
+use crate::misc::request_id::{current_request_id, with_request_id};
+use crate::misc::rpc_guard;
+use crate::misc::rpc_pool::RPC_POOL;
+
+const MEVLOG_BIN: &str = "mevlog";
+// How long a process that closed its output, or was sent SIGTERM, may take
+// to exit before it's killed
+const EXIT_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How long a run, or the wait for its next line, may take.
+pub fn mevlog_timeout() -> Duration {
+    Duration::from_secs(app_config().mevlog.timeout_secs)
+}
+
+/// A single mevlog invocation, kept as plain arguments so every call site
+/// builds it the same way and runs it through one execution path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MevlogCmd {
+    args: Vec<String>,
+    chain_id: Option<u64>,
+    trusted_rpc: bool,
+}
+
+impl MevlogCmd {
+    pub fn new(subcommand: &str) -> Self {
+        Self {
+            args: vec![subcommand.to_string()],
+            chain_id: None,
+            trusted_rpc: false,
+        }
+    }
+
+    /// Tags the command with the chain it queries, used for per-chain limits.
+    /// Doesn't add any arguments.
+    pub fn chain(&mut self, chain_id: u64) -> &mut Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Marks the RPC as the operator's own, which may be on a private
+    /// network, so the run connects directly instead of through
+    /// [`rpc_guard`]. Doesn't add any arguments.
+    pub fn trust_rpc(&mut self) -> &mut Self {
+        self.trusted_rpc = true;
+        self
+    }
+
+    pub fn chain_id(&self) -> Option<u64> {
+        self.chain_id
+    }
+
+    pub fn is_trusted_rpc(&self) -> bool {
+        self.trusted_rpc
+    }
+
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn subcommand(&self) -> &str {
+        self.args.first().map(String::as_str).unwrap_or_default()
+    }
+
+    /// Returns the value following `flag`, if present.
+    pub fn flag_value(&self, flag: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .position(|arg| arg == flag)
+            .and_then(|idx| self.args.get(idx + 1))
+            .map(String::as_str)
+    }
+
+    /// The RPC endpoint the command was pointed at, if any.
+    pub fn rpc_url(&self) -> Option<&str> {
+        self.flag_value("--rpc-url")
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.args.iter().any(|arg| arg == flag)
+    }
+
+    /// Arguments with the RPC URL cut down to its host, as it can carry an
+    /// API key.
+    pub fn redacted_args(&self) -> Vec<String> {
+        let mut args = self.args.clone();
+        if let Some(idx) = args.iter().position(|arg| arg == "--rpc-url")
+            && let Some(url) = args.get_mut(idx + 1)
+        {
+            *url = match url::Url::parse(url) {
+                Ok(parsed) => format!(
+                    "{}://{}/…",
+                    parsed.scheme(),
+                    parsed.host_str().unwrap_or_default()
+                ),
+                Err(_) => "…".to_string(),
+            };
+        }
+        args
+    }
+
+    fn to_command(&self, bin: &str) -> Result<Command, MevlogError> {
+        let mut cmd = Command::new(bin);
+        cmd.args(&self.args);
+        cmd.env("RUST_LOG", "off");
+        // Lets the child's own reporting be matched with the request
+        if let Some(request_id) = current_request_id() {
+            cmd.env("MEVLOG_REQUEST_ID", request_id);
+        }
+        if RPC_POLICY.block_private_ips && !self.trusted_rpc {
+            let proxy = rpc_guard::proxy_url().map_err(MevlogError::Spawn)?;
+            cmd.env("HTTPS_PROXY", proxy)
+                .env("HTTP_PROXY", proxy)
+                .env_remove("https_proxy")
+                .env_remove("http_proxy")
+                .env_remove("ALL_PROXY")
+                .env_remove("all_proxy")
+                .env_remove("NO_PROXY")
+                .env_remove("no_proxy");
+        }
+        // Last resort if a handle is dropped without going through `terminate`
+        cmd.kill_on_drop(true);
+        Ok(cmd)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MevlogError {
+    /// The mevlog process could not be started.
+    Spawn(String),
+    /// mevlog exited with an error, holds the user-facing stderr message.
+    Failed(String),
+    /// The output could not be parsed into the expected type.
+    Parse(String),
+    /// mevlog finished without printing anything.
+    NoOutput,
+    /// The run did not finish within the time limit.
+    Timeout,
+    /// Too many runs are in progress or queued.
+    Busy { retry_after: u64 },
+    /// The RPC URL the user supplied was refused, holds the reason.
+    RpcRejected(String),
+}
+
+impl fmt::Display for MevlogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn(e) => write!(f, "Failed to spawn command: {e}"),
+            Self::Failed(e) => write!(f, "{e}"),
+            Self::Parse(e) => write!(f, "Failed to parse JSON: {e}"),
+            Self::NoOutput => write!(f, "No output received from command"),
+            Self::Timeout => write!(f, "{DATA_FETCH_ERROR}"),
+            Self::Busy { retry_after } => {
+                write!(f, "Server busy, please retry in {retry_after}s.")
+            }
+            Self::RpcRejected(e) => write!(f, "Invalid rpc_url: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MevlogError {}
+
+impl MevlogError {
+    /// Whether the run failed because the RPC couldn't serve the data, the
+    /// cases shown to users as [`DATA_FETCH_ERROR`].
+    pub fn is_rpc_failure(&self) -> bool {
+        match self {
+            Self::Timeout => true,
+            Self::Failed(e) => e == DATA_FETCH_ERROR,
+            _ => false,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let error = match self {
+            Self::Busy { retry_after } => serde_json::json!({
+                "error": self.to_string(),
+                "retry_after": retry_after
+            }),
+            _ => serde_json::json!({
+                "error": self.to_string()
+            }),
+        };
+        with_request_id(error)
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Busy { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for MevlogError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status_code(), Json(self.to_json())).into_response();
+        if let Self::Busy { retry_after } = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
+
+/// Executes mevlog commands. Production runs them through
+/// [`crate::misc::mevlog_lib::LibraryRunner`], tests replay recorded output
+/// through [`crate::misc::fixture_runner::FixtureRunner`].
+pub trait MevlogRunner: Send + Sync {
+    /// Runs the command to completion and returns its whole stdout.
+    fn output(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>>;
+
+    /// Returns the first stdout line without waiting for the command to exit.
+    fn first_line(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>>;
+
+    /// Starts the command and streams its stdout and stderr lines.
+    fn stream(&self, cmd: &MevlogCmd) -> Result<MevlogStream, MevlogError>;
+}
+
+pub type LineStream = BoxStream<'static, std::io::Result<String>>;
+
+pub struct MevlogStream {
+    pub stdout: LineStream,
+    pub stderr: LineStream,
+    child: Option<Child>,
+    _permit: Option<PoolPermit>,
+    /// Chain and RPC the run reads from, scored when the stream finishes.
+    rpc: Option<(u64, String)>,
+    rpc_error: Option<String>,
+    /// Subcommand, chain and start of the run, for [`METRICS`].
+    run: Option<(String, Option<u64>, Instant)>,
+    _running: Option<RunningGuard>,
+}
+
+impl MevlogStream {
+    pub fn new(stdout: LineStream, stderr: LineStream) -> Self {
+        Self {
+            stdout,
+            stderr,
+            child: None,
+            _permit: None,
+            rpc: None,
+            rpc_error: None,
+            run: None,
+            _running: None,
+        }
+    }
+
+    /// Marks the run as failed because of its RPC, see
+    /// [`MevlogError::is_rpc_failure`].
+    pub fn record_rpc_failure(&mut self, error: impl Into<String>) {
+        self.rpc_error = Some(error.into());
+    }
+
+    /// Stops the underlying process, if any, and records why the stream ended.
+    pub async fn finish(self, end: RunEnd) {
+        if let Some((chain_id, url)) = &self.rpc {
+            match (&self.rpc_error, end) {
+                (Some(error), _) => RPC_POOL.record_failure(*chain_id, url, error),
+                (None, RunEnd::TimedOut) => {
+                    RPC_POOL.record_failure(*chain_id, url, &RunEnd::TimedOut.to_string())
+                }
+                (None, RunEnd::Completed) => RPC_POOL.record_success(*chain_id, url, None),
+                _ => {}
+            }
+        }
+
+        if let Some((command, chain_id, started_at)) = &self.run {
+            let failed = self.rpc_error.is_some() || end == RunEnd::TimedOut;
+            METRICS.record_mevlog_run(command, *chain_id, started_at.elapsed(), failed);
+        }
+
+        match self.child {
+            Some(child) => terminate(child, end).await,
+            None => tracing::info!("mevlog stream ended: {}", end),
+        }
+    }
+}
+
+/// Why a consumer stopped reading from a mevlog process.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunEnd {
+    /// The process closed its output on its own.
+    Completed,
+    /// Only the first line was needed.
+    FirstLineRead,
+    /// The client went away before the process finished.
+    ClientDisconnected,
+    /// The client asked to stop the run.
+    Cancelled,
+    /// The process ran past its time limit.
+    TimedOut,
+    /// The server is shutting down.
+    ShuttingDown,
+}
+
+impl fmt::Display for RunEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Completed => write!(f, "completed"),
+            Self::FirstLineRead => write!(f, "first line read"),
+            Self::ClientDisconnected => write!(f, "client disconnected"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::ShuttingDown => write!(f, "server restarting"),
+        }
+    }
+}
+
+/// Waits for the process to exit, asking it to with SIGTERM unless it
+/// completed, and kills it if it's still running after
+/// [`EXIT_GRACE_PERIOD`]. Reaps it either way.
+async fn terminate(mut child: Child, end: RunEnd) {
+    let pid = child.id();
+
+    if end != RunEnd::Completed {
+        send_sigterm(&child);
+    }
+    if let Ok(Ok(status)) = timeout(EXIT_GRACE_PERIOD, child.wait()).await {
+        tracing::info!("mevlog pid {:?} {}, exit status: {}", pid, end, status);
+        return;
+    }
+
+    match child.kill().await {
+        Ok(_) => tracing::info!("mevlog pid {:?} killed, {}", pid, end),
+        Err(e) => tracing::error!("Failed to kill mevlog pid {:?} ({}): {}", pid, end, &e),
+    }
+}
+
+#[cfg(unix)]
+fn send_sigterm(child: &Child) {
+    let Some(pid) = child.id() else {
+        return;
+    };
+    // SAFETY: `kill` only takes plain integers. The child isn't reaped
+    // until `terminate` waits on it, so the pid can't have been reused.
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        tracing::error!(
+            "Failed to send SIGTERM to mevlog pid {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn send_sigterm(_child: &Child) {}
+
+/// A mevlog run in progress.
+#[derive(Debug, Clone)]
+pub struct RunningProcess {
+    /// See [`MevlogCmd::redacted_args`].
+    pub args: Vec<String>,
+    pub chain_id: Option<u64>,
+    pub started_at: Instant,
+}
+
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(0);
+static RUNNING: LazyLock<Mutex<BTreeMap<u64, RunningProcess>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+static RUN_FINISHED: Notify = Notify::const_new();
+
+/// Lists the run in [`running_processes`] until dropped.
+struct RunningGuard(u64);
+
+impl RunningGuard {
+    fn start(cmd: &MevlogCmd) -> Self {
+        let id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
+        RUNNING.lock().expect("running runs lock poisoned").insert(
+            id,
+            RunningProcess {
+                args: cmd.redacted_args(),
+                chain_id: cmd.chain_id(),
+                started_at: Instant::now(),
+            },
+        );
+        Self(id)
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING
+            .lock()
+            .expect("running runs lock poisoned")
+            .remove(&self.0);
+        RUN_FINISHED.notify_waiters();
+    }
+}
+
+/// Waits up to `limit` for the runs in progress to finish and their
+/// processes to be reaped, returns whether they all did.
+pub async fn wait_for_runs(limit: Duration) -> bool {
+    timeout(limit, async {
+        loop {
+            let finished = RUN_FINISHED.notified();
+            if RUNNING
+                .lock()
+                .expect("running runs lock poisoned")
+                .is_empty()
+            {
+                return;
+            }
+            finished.await;
+        }
+    })
+    .await
+    .is_ok()
+}
+
+/// mevlog runs in progress, longest running first.
+pub fn running_processes() -> Vec<RunningProcess> {
+    let mut running: Vec<RunningProcess> = RUNNING
+        .lock()
+        .expect("running runs lock poisoned")
+        .values()
+        .cloned()
+        .collect();
+    running.sort_by_key(|process| process.started_at);
+    running
+}
+
+static RUNNER: LazyLock<RwLock<Arc<dyn MevlogRunner>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(LibraryRunner::default())));
+
+pub fn runner() -> Arc<dyn MevlogRunner> {
+    RUNNER.read().expect("mevlog runner lock poisoned").clone()
+}
+
+pub fn set_runner(runner: Arc<dyn MevlogRunner>) {
+    *RUNNER.write().expect("mevlog runner lock poisoned") = runner;
+}
+
+/// Runs the command to completion and parses its whole stdout.
+#[hotpath::measure]
+pub async fn run_json<T: DeserializeOwned>(cmd: &MevlogCmd) -> Result<T, MevlogError> {
+    tracing::trace!("mevlog: {:?}", cmd.args());
+    let _permit = MEVLOG_POOL.acquire(cmd.chain_id()).await?;
+    let _running = RunningGuard::start(cmd);
+    let started_at = Instant::now();
+    let stdout = runner().output(cmd).await;
+    record_run(cmd, started_at, &stdout);
+    serde_json::from_str::<T>(&stdout?).map_err(|e| MevlogError::Parse(e.to_string()))
+}
+
+/// Parses the first line mevlog prints, without waiting for the process to exit.
+pub async fn run_json_first_line<T: DeserializeOwned>(cmd: &MevlogCmd) -> Result<T, MevlogError> {
+    tracing::trace!("mevlog: {:?}", cmd.args());
+    let _permit = MEVLOG_POOL.acquire(cmd.chain_id()).await?;
+    let _running = RunningGuard::start(cmd);
+    let started_at = Instant::now();
+    let line = runner().first_line(cmd).await;
+    record_run(cmd, started_at, &line);
+    serde_json::from_str::<T>(&line?).map_err(|e| MevlogError::Parse(e.to_string()))
+}
+
+/// Starts the command and exposes its stdout and stderr as line streams.
+/// The pool slot is held until the stream is finished or dropped.
+#[hotpath::measure]
+pub async fn run_stream(cmd: &MevlogCmd) -> Result<MevlogStream, MevlogError> {
+    tracing::trace!("mevlog: {:?}", cmd.args());
+    let permit = MEVLOG_POOL.acquire(cmd.chain_id()).await?;
+    let started_at = Instant::now();
+    let mut stream = runner().stream(cmd).inspect_err(|_| {
+        METRICS.record_mevlog_run(cmd.subcommand(), cmd.chain_id(), started_at.elapsed(), true)
+    })?;
+    stream._permit = Some(permit);
+    stream.rpc = cmd.chain_id().zip(cmd.rpc_url().map(str::to_string));
+    stream.run = Some((cmd.subcommand().to_string(), cmd.chain_id(), started_at));
+    stream._running = Some(RunningGuard::start(cmd));
+    Ok(stream)
+}
+
+fn record_run(cmd: &MevlogCmd, started_at: Instant, result: &Result<String, MevlogError>) {
+    RPC_POOL.record_run(cmd, result);
+    METRICS.record_mevlog_run(
+        cmd.subcommand(),
+        cmd.chain_id(),
+        started_at.elapsed(),
+        result.is_err(),
+    );
+}
+
+/// Runs mevlog as a child process.
+pub struct ProcessRunner {
+    bin: String,
+}
+
+impl Default for ProcessRunner {
+    fn default() -> Self {
+        Self::with_bin(MEVLOG_BIN)
+    }
+}
+
+impl ProcessRunner {
+    /// Runs `bin` in place of mevlog, e.g. a stand-in script.
+    pub fn with_bin(bin: impl Into<String>) -> Self {
+        Self { bin: bin.into() }
+    }
+}
+
+impl MevlogRunner for ProcessRunner {
+    fn output(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>> {
+        let mut command = match cmd.to_command(&self.bin) {
+            Ok(command) => command,
+            Err(e) => return futures::future::ready(Err(e)).boxed(),
+        };
+
+        async move {
+            let output = match timeout(mevlog_timeout(), command.output()).await {
+                Ok(Ok(output)) => output,
+                Ok(Err(e)) => return Err(MevlogError::Spawn(e.to_string())),
+                Err(_) => return Err(MevlogError::Timeout),
+            };
+
+            if !output.status.success() {
+                let error_msg = String::from_utf8_lossy(&output.stderr);
+                return Err(MevlogError::Failed(decorate_error_message(&error_msg)));
+            }
+
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        .boxed()
+    }
+
+    fn first_line(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>> {
+        let mut command = match cmd.to_command(&self.bin) {
+            Ok(command) => command,
+            Err(e) => return futures::future::ready(Err(e)).boxed(),
+        };
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        async move {
+            let mut child = command
+                .spawn()
+                .map_err(|e| MevlogError::Spawn(e.to_string()))?;
+
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| MevlogError::Spawn("Failed to capture stdout".to_string()))?;
+
+            let mut reader = BufReader::new(stdout).lines();
+
+            let next_line_future = hotpath::future!(reader.next_line(), log = true);
+
+            let (result, end) = match timeout(mevlog_timeout(), next_line_future).await {
+                Ok(Ok(Some(line))) => (Ok(line), RunEnd::FirstLineRead),
+                Ok(Ok(None)) => (Err(MevlogError::NoOutput), RunEnd::Completed),
+                Ok(Err(e)) => (Err(MevlogError::Parse(e.to_string())), RunEnd::Completed),
+                Err(_) => (Err(MevlogError::Timeout), RunEnd::TimedOut),
+            };
+
+            terminate(child, end).await;
+            result
+        }
+        .boxed()
+    }
+
+    fn stream(&self, cmd: &MevlogCmd) -> Result<MevlogStream, MevlogError> {
+        let mut child = cmd
+            .to_command(&self.bin)?
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| MevlogError::Spawn(e.to_string()))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| MevlogError::Spawn("Failed to capture stdout".to_string()))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| MevlogError::Spawn("Failed to capture stderr".to_string()))?;
+
+        let mut stream = MevlogStream::new(
+            LinesStream::new(BufReader::new(stdout).lines()).boxed(),
+            LinesStream::new(BufReader::new(stderr).lines()).boxed(),
+        );
+        stream.child = Some(child);
+        Ok(stream)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_rpc_url() {
+        let mut cmd = MevlogCmd::new("search");
+        cmd.arg("--rpc-url")
+            .arg("https://eth.example.org/v2/secret-key")
+            .arg("-b")
+            .arg("latest");
+
+        assert_eq!(
+            cmd.redacted_args(),
+            vec![
+                "search",
+                "--rpc-url",
+                "https://eth.example.org/…",
+                "-b",
+                "latest"
+            ]
+        );
+    }
+
+    /// A stand-in for mevlog that prints its pid, then runs `rest`.
+    fn script(rest: &str) -> (ProcessRunner, MevlogCmd) {
+        let mut cmd = MevlogCmd::new("-c");
+        cmd.arg(format!("echo $$; {rest}"));
+        (ProcessRunner::with_bin("sh"), cmd)
+    }
+
+    // Also false for zombies, so it checks the child was reaped too
+    fn is_running(pid: &str) -> bool {
+        std::path::Path::new(&format!("/proc/{pid}")).exists()
+    }
+
+    #[tokio::test]
+    async fn first_line_kills_the_process() {
+        let (runner, cmd) = script("exec sleep 30");
+
+        let pid = runner.first_line(&cmd).await.unwrap();
+
+        assert!(!is_running(&pid));
+    }
+
+    #[tokio::test]
+    async fn abandoned_stream_kills_the_process() {
+        for end in [
+            RunEnd::ClientDisconnected,
+            RunEnd::TimedOut,
+            RunEnd::Cancelled,
+        ] {
+            let (runner, cmd) = script("exec sleep 30");
+            let mut stream = runner.stream(&cmd).unwrap();
+            let pid = stream.stdout.next().await.unwrap().unwrap();
+            assert!(is_running(&pid));
+
+            stream.finish(end).await;
+
+            assert!(!is_running(&pid), "{end}");
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_stream_gets_sigterm_first() {
+        let marker = std::env::temp_dir().join(format!("mevlog-sigterm-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let (runner, cmd) = script(&format!(
+            "trap 'touch {}; exit 0' TERM; while true; do sleep 0.1; done",
+            marker.display()
+        ));
+        let mut stream = runner.stream(&cmd).unwrap();
+        let pid = stream.stdout.next().await.unwrap().unwrap();
+        // The pid is printed before the trap is set
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        stream.finish(RunEnd::Cancelled).await;
+
+        assert!(!is_running(&pid));
+        assert!(marker.exists(), "the process wasn't asked to stop");
+        let _ = std::fs::remove_file(&marker);
+    }
+
+    #[tokio::test]
+    async fn completed_stream_is_reaped() {
+        let (runner, cmd) = script("echo done");
+        let mut stream = runner.stream(&cmd).unwrap();
+        let pid = stream.stdout.next().await.unwrap().unwrap();
+        while stream.stdout.next().await.is_some() {}
+
+        stream.finish(RunEnd::Completed).await;
+
+        assert!(!is_running(&pid));
+    }
+}
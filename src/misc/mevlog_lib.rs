@@ -0,0 +1,462 @@
+use clap::Parser;
+use eyre::{Result, bail};
+use futures::{FutureExt, StreamExt, future::BoxFuture};
+use mevlog::{
+    ChainEntryJson, ChainInfoJson, ChainInfoNoRpcsJson, RpcUrlInfo,
+    misc::{
+        args_parsing::BlocksRange,
+        data_fetch::fetch_blocks_batch,
+        ens_utils::ENSLookup,
+        rpc_urls::{
+            ChainInfo, get_all_chains, get_chain_id_from_rpc, get_chain_info,
+            get_chain_info_no_benchmark,
+        },
+        shared_init::{ConnOpts, OutputFormat, SharedOpts, init_deps},
+        symbol_utils::ERC20SymbolsLookup,
+        utils::get_native_token_price,
+    },
+    models::{
+        mev_block::{PreFetchedBlockData, generate_block},
+        txs_filter::{TxsFilter, TxsFilterOpts},
+    },
+};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::controllers::base_controller::decorate_error_message;
+use crate::misc::custom_rpc::RPC_POLICY;
+use crate::misc::mevlog_cmd::{
+    MevlogCmd, MevlogError, MevlogRunner, MevlogStream, ProcessRunner, mevlog_timeout,
+};
+use crate::misc::request_id::RequestScope;
+
+/// Blocks a streamed search may get ahead of its reader.
+const LINES_BUFFER: usize = 16;
+
+/// Runs `search`, `chain-info` and `chains` through the mevlog library in
+/// the server process, and prints the same JSON the CLI would. Other
+/// commands, and runs [`LibraryCmd::parse`] leaves out, spawn the binary
+/// through [`ProcessRunner`].
+#[derive(Default)]
+pub struct LibraryRunner {
+    process: ProcessRunner,
+}
+
+impl MevlogRunner for LibraryRunner {
+    fn output(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>> {
+        let Some(library_cmd) = LibraryCmd::parse(cmd) else {
+            return self.process.output(cmd);
+        };
+
+        async move {
+            let (mut stdout, mut stderr) = spawn_run(library_cmd);
+            let read_all = async {
+                let mut lines = vec![];
+                while let Some(line) = stdout.recv().await {
+                    lines.push(line);
+                }
+                lines
+            };
+
+            let lines = timeout(mevlog_timeout(), read_all)
+                .await
+                .map_err(|_| MevlogError::Timeout)?;
+            match stderr.try_recv() {
+                Ok(error) => Err(failed(&error)),
+                Err(_) => Ok(lines.join("\n")),
+            }
+        }
+        .boxed()
+    }
+
+    fn first_line(&self, cmd: &MevlogCmd) -> BoxFuture<'static, Result<String, MevlogError>> {
+        let Some(library_cmd) = LibraryCmd::parse(cmd) else {
+            return self.process.first_line(cmd);
+        };
+
+        // Dropping the output once the line is in stops the run
+        async move {
+            let (mut stdout, mut stderr) = spawn_run(library_cmd);
+            match timeout(mevlog_timeout(), stdout.recv()).await {
+                Ok(Some(line)) => Ok(line),
+                Ok(None) => Err(stderr
+                    .try_recv()
+                    .map(|error| failed(&error))
+                    .unwrap_or(MevlogError::NoOutput)),
+                Err(_) => Err(MevlogError::Timeout),
+            }
+        }
+        .boxed()
+    }
+
+    fn stream(&self, cmd: &MevlogCmd) -> Result<MevlogStream, MevlogError> {
+        let Some(library_cmd) = LibraryCmd::parse(cmd) else {
+            return self.process.stream(cmd);
+        };
+
+        let (stdout, stderr) = spawn_run(library_cmd);
+        Ok(MevlogStream::new(
+            ReceiverStream::new(stdout).map(Ok).boxed(),
+            ReceiverStream::new(stderr).map(Ok).boxed(),
+        ))
+    }
+}
+
+fn failed(error: &str) -> MevlogError {
+    MevlogError::Failed(decorate_error_message(error))
+}
+
+/// Starts the run on a blocking thread, as the library makes blocking calls
+/// like running `cryo`. Returns the lines the CLI would print to stdout, and
+/// its error message if it fails. The run stops once stdout is dropped.
+fn spawn_run(cmd: LibraryCmd) -> (mpsc::Receiver<String>, mpsc::Receiver<String>) {
+    let (stdout_tx, stdout) = mpsc::channel(LINES_BUFFER);
+    let (stderr_tx, stderr) = mpsc::channel(1);
+    let runtime = Handle::current();
+    let scope = RequestScope::current();
+
+    tokio::task::spawn_blocking(move || {
+        runtime.block_on(scope.run(async move {
+            let result = tokio::select! {
+                result = cmd.run(&stdout_tx) => result,
+                _ = stdout_tx.closed() => Ok(()),
+            };
+            if let Err(e) = result {
+                let _ = stderr_tx.send(e.to_string()).await;
+            }
+        }))
+    });
+
+    (stdout, stderr)
+}
+
+/// The part of mevlog's command line the library runs, see
+/// [`MevlogCmd`]. Unknown flags fail to parse, and the command spawns the
+/// binary instead.
+#[derive(Debug, Parser)]
+#[command(no_binary_name = true)]
+enum LibraryCmd {
+    Search(Box<SearchArgs>),
+    ChainInfo(ChainInfoArgs),
+    Chains(ChainsArgs),
+}
+
+impl LibraryCmd {
+    /// Parses commands asking for JSON that can run in-process. Runs that
+    /// connect to an RPC while private addresses are blocked are left out,
+    /// unless it's the operator's own: the library and `cryo` make their
+    /// own connections, which only a child process can route through
+    /// [`crate::misc::rpc_guard`].
+    fn parse(cmd: &MevlogCmd) -> Option<Self> {
+        let parsed = Self::try_parse_from(cmd.args()).ok()?;
+        let (format, connects_to_rpc) = match &parsed {
+            Self::Search(args) => (&args.format, true),
+            Self::ChainInfo(args) => (&args.format, !args.skip_urls || args.chain_id.is_none()),
+            Self::Chains(args) => (&args.format, false),
+        };
+
+        let json = matches!(format, OutputFormat::Json | OutputFormat::JsonStream);
+        let guarded = connects_to_rpc && RPC_POLICY.block_private_ips && !cmd.is_trusted_rpc();
+        (json && !guarded).then_some(parsed)
+    }
+
+    async fn run(self, stdout: &mpsc::Sender<String>) -> Result<()> {
+        let output = match self {
+            Self::Search(args) => return args.run(stdout).await,
+            Self::ChainInfo(args) => args.run().await?,
+            Self::Chains(args) => args.run().await?,
+        };
+        let _ = stdout.send(output).await;
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct SearchArgs {
+    #[arg(short = 'b', long)]
+    blocks: String,
+
+    #[arg(long)]
+    limit: Option<usize>,
+
+    #[command(flatten)]
+    filter_opts: TxsFilterOpts,
+
+    #[command(flatten)]
+    shared_opts: SharedOpts,
+
+    #[command(flatten)]
+    conn_opts: ConnOpts,
+
+    #[arg(long)]
+    latest_offset: Option<u64>,
+
+    #[arg(long)]
+    max_range: Option<u64>,
+
+    #[arg(long, default_value = "100")]
+    batch_size: usize,
+
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+impl SearchArgs {
+    /// Same as `mevlog search`: each block's transactions as a line with
+    /// `json-stream`, all of them at the end with `json`.
+    async fn run(&self, stdout: &mpsc::Sender<String>) -> Result<()> {
+        if self.limit.is_some() && self.format != OutputFormat::Json {
+            bail!("--limit is not available in --format {:?}", self.format);
+        }
+
+        let deps = init_deps(&self.conn_opts).await?;
+        let txs_filter = TxsFilter::new(&self.filter_opts, None, &self.shared_opts, false, None)?;
+
+        let ens_query = txs_filter
+            .from_ens_query()
+            .or_else(|| txs_filter.to_ens_query());
+        let ens_lookup = ENSLookup::lookup_mode(
+            ens_query,
+            deps.ens_lookup_worker,
+            &deps.chain,
+            self.shared_opts.ens,
+            &deps.provider,
+        )
+        .await?;
+        let symbols_lookup = ERC20SymbolsLookup::lookup_mode(
+            deps.symbols_lookup_worker,
+            self.shared_opts.erc20_symbols,
+        );
+
+        let native_token_price = get_native_token_price(
+            &deps.chain,
+            &deps.provider,
+            self.shared_opts.native_token_price,
+        )
+        .await?;
+
+        let block_range =
+            BlocksRange::from_str(&self.blocks, &deps.provider, self.latest_offset).await?;
+        if let Some(max_range) = self.max_range
+            && block_range.size() > max_range
+        {
+            bail!(
+                "Block range size {} exceeds maximum allowed range of {}",
+                block_range.size(),
+                max_range
+            );
+        }
+
+        let mut transactions = vec![];
+        let blocks: Vec<u64> = (block_range.from..=block_range.to).rev().collect();
+        for chunk in blocks.chunks(self.batch_size.max(1)) {
+            let (Some(&start_block), Some(&end_block)) = (chunk.last(), chunk.first()) else {
+                continue;
+            };
+            let mut batch_data = fetch_blocks_batch(
+                start_block,
+                end_block,
+                &deps.chain,
+                &deps.sqlite,
+                &symbols_lookup,
+                txs_filter.show_erc20_transfer_amount,
+            )
+            .await?;
+
+            for &block_number in chunk {
+                let pre_fetched = PreFetchedBlockData {
+                    txs_data: batch_data
+                        .txs_by_block
+                        .remove(&block_number)
+                        .unwrap_or_default(),
+                    logs_data: batch_data
+                        .logs_by_block
+                        .remove(&block_number)
+                        .unwrap_or_default(),
+                };
+
+                let mev_block = generate_block(
+                    &deps.provider,
+                    &deps.sqlite,
+                    block_number,
+                    &ens_lookup,
+                    &txs_filter,
+                    &self.shared_opts,
+                    &deps.chain,
+                    &deps.rpc_url,
+                    native_token_price,
+                    pre_fetched,
+                )
+                .await?;
+
+                if self.format == OutputFormat::JsonStream {
+                    let line = serde_json::to_string(&mev_block.transactions_json())?;
+                    if stdout.send(line).await.is_err() {
+                        return Ok(());
+                    }
+                } else {
+                    transactions.extend(mev_block.transactions_json());
+                }
+            }
+        }
+
+        if self.format == OutputFormat::Json {
+            if let Some(limit) = self.limit {
+                transactions.truncate(limit);
+            }
+            let _ = stdout.send(serde_json::to_string(&transactions)?).await;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct ChainInfoArgs {
+    #[arg(long)]
+    skip_urls: bool,
+
+    #[arg(long)]
+    chain_id: Option<u64>,
+
+    #[arg(long)]
+    rpc_url: Option<String>,
+
+    #[arg(long, default_value = "1000")]
+    rpc_timeout_ms: u64,
+
+    #[arg(long, default_value = "5")]
+    rpcs_limit: usize,
+
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+impl ChainInfoArgs {
+    async fn run(&self) -> Result<String> {
+        let chain_id = match (self.chain_id, &self.rpc_url) {
+            (Some(chain_id), _) => chain_id,
+            (None, Some(rpc_url)) => get_chain_id_from_rpc(rpc_url).await?,
+            (None, None) => bail!("Either --chain-id or --rpc-url must be specified"),
+        };
+
+        if self.skip_urls {
+            let chain = get_chain_info_no_benchmark(chain_id).await?;
+            return Ok(serde_json::to_string(&ChainInfoNoRpcsJson {
+                chain_id,
+                name: chain.name.clone(),
+                currency: chain.native_currency.symbol.clone(),
+                explorer_url: explorer_url(&chain),
+            })?);
+        }
+
+        let chain = get_chain_info(chain_id, self.rpc_timeout_ms, self.rpcs_limit).await?;
+        if chain.benchmarked_rpc_urls.is_empty() {
+            bail!("No working RPC URLs found for chain ID {}", chain_id);
+        }
+        Ok(serde_json::to_string(&ChainInfoJson {
+            chain_id,
+            name: chain.name.clone(),
+            currency: chain.native_currency.symbol.clone(),
+            explorer_url: explorer_url(&chain),
+            rpc_timeout_ms: self.rpc_timeout_ms,
+            rpc_urls: chain
+                .benchmarked_rpc_urls
+                .iter()
+                .map(|(url, response_time_ms)| RpcUrlInfo {
+                    url: url.clone(),
+                    response_time_ms: *response_time_ms,
+                })
+                .collect(),
+        })?)
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct ChainsArgs {
+    #[arg(long, short = 'f')]
+    filter: Option<String>,
+
+    #[arg(long, short = 'l')]
+    limit: Option<usize>,
+
+    #[arg(long, action = clap::ArgAction::Append)]
+    chain_id: Vec<u64>,
+
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+impl ChainsArgs {
+    async fn run(&self) -> Result<String> {
+        let mut chains = get_all_chains().await?;
+
+        if let Some(filter) = &self.filter {
+            let filter = filter.to_lowercase();
+            chains.retain(|chain| {
+                chain.name.to_lowercase().contains(&filter)
+                    || chain.chain.to_lowercase().contains(&filter)
+            });
+        }
+        if !self.chain_id.is_empty() {
+            chains.retain(|chain| self.chain_id.contains(&chain.chain_id));
+        }
+        chains.sort_by_key(|chain| chain.chain_id);
+        if let Some(limit) = self.limit {
+            chains.truncate(limit);
+        }
+
+        let entries: Vec<ChainEntryJson> = chains
+            .iter()
+            .map(|chain| ChainEntryJson {
+                chain_id: chain.chain_id,
+                name: chain.name.clone(),
+                chain: chain.chain.clone(),
+                explorer_url: explorer_url(chain),
+            })
+            .collect();
+        Ok(serde_json::to_string(&entries)?)
+    }
+}
+
+fn explorer_url(chain: &ChainInfo) -> Option<String> {
+    chain.explorers.first().map(|explorer| explorer.url.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Option<LibraryCmd> {
+        let mut cmd = MevlogCmd::new(args[0]);
+        for arg in &args[1..] {
+            cmd.arg(*arg);
+        }
+        LibraryCmd::parse(&cmd)
+    }
+
+    #[test]
+    fn runs_json_commands_in_process() {
+        assert!(matches!(
+            parse(&[
+                "chain-info",
+                "--chain-id",
+                "10",
+                "--format",
+                "json",
+                "--skip-urls"
+            ]),
+            Some(LibraryCmd::ChainInfo(_))
+        ));
+        assert!(matches!(
+            parse(&["chains", "--format", "json", "--chain-id", "1", "--chain-id", "10"]),
+            Some(LibraryCmd::Chains(args)) if args.chain_id == [1, 10]
+        ));
+
+        // Text output and flags the library runner doesn't know spawn the binary
+        assert!(parse(&["chain-info", "--chain-id", "10", "--skip-urls"]).is_none());
+        assert!(parse(&["search", "-b", "latest", "--sort", "gas-price"]).is_none());
+        assert!(parse(&["tx", "0xabc", "--format", "json"]).is_none());
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use crate::config::app_config::{MevlogConfig, app_config};
+use crate::misc::mevlog_cmd::MevlogError;
+
+pub static MEVLOG_POOL: LazyLock<MevlogPool> =
+    LazyLock::new(|| MevlogPool::new(PoolLimits::from_config(&app_config().mevlog)));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolLimits {
+    /// mevlog runs allowed at the same time across all chains.
+    pub max_concurrent: usize,
+    /// Runs allowed at the same time for a single chain.
+    pub max_per_chain: usize,
+    /// Requests allowed to wait for a free slot before new ones are rejected.
+    pub max_queued: usize,
+    /// How long a queued request waits for a slot before giving up.
+    pub max_wait: Duration,
+}
+
+impl PoolLimits {
+    pub fn from_config(config: &MevlogConfig) -> Self {
+        Self {
+            max_concurrent: config.max_concurrent,
+            max_per_chain: config.max_per_chain,
+            max_queued: config.max_queued,
+            max_wait: Duration::from_secs(config.max_wait_secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStats {
+    pub running: usize,
+    pub queued: usize,
+    pub rejected: u64,
+    pub avg_wait_ms: u64,
+    pub avg_run_ms: u64,
+    pub limits: PoolLimits,
+}
+
+/// Bounds how many mevlog processes run at once, globally and per chain.
+/// Requests beyond the limits wait in a bounded queue and are turned away
+/// with [`MevlogError::Busy`] once it's full.
+pub struct MevlogPool {
+    limits: PoolLimits,
+    global: Arc<Semaphore>,
+    per_chain: Mutex<HashMap<u64, Arc<Semaphore>>>,
+    queued: AtomicUsize,
+    running: Arc<AtomicUsize>,
+    rejected: AtomicU64,
+    avg_wait_ms: AtomicU64,
+    avg_run_ms: Arc<AtomicU64>,
+}
+
+/// A slot in the pool, released when dropped.
+pub struct PoolPermit {
+    _global: OwnedSemaphorePermit,
+    _chain: Option<OwnedSemaphorePermit>,
+    running: Arc<AtomicUsize>,
+    avg_run_ms: Arc<AtomicU64>,
+    started_at: Instant,
+}
+
+impl Drop for PoolPermit {
+    fn drop(&mut self) {
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        record_avg(&self.avg_run_ms, self.started_at.elapsed());
+    }
+}
+
+impl MevlogPool {
+    pub fn new(limits: PoolLimits) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(limits.max_concurrent)),
+            limits,
+            per_chain: Mutex::new(HashMap::new()),
+            queued: AtomicUsize::new(0),
+            running: Arc::new(AtomicUsize::new(0)),
+            rejected: AtomicU64::new(0),
+            avg_wait_ms: AtomicU64::new(0),
+            avg_run_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub async fn acquire(&self, chain_id: Option<u64>) -> Result<PoolPermit, MevlogError> {
+        let chain = chain_id.map(|chain_id| self.chain_semaphore(chain_id));
+
+        if let Some(permit) = self.try_acquire(chain.as_ref()) {
+            return Ok(permit);
+        }
+
+        let queued = QueuedGuard::new(&self.queued);
+        if queued.position >= self.limits.max_queued {
+            drop(queued);
+            return Err(self.reject(chain_id));
+        }
+
+        let wait_start = Instant::now();
+        let acquired = timeout(self.limits.max_wait, async {
+            let chain_permit = match &chain {
+                Some(semaphore) => Some(semaphore.clone().acquire_owned().await.ok()?),
+                None => None,
+            };
+            let global_permit = self.global.clone().acquire_owned().await.ok()?;
+            Some((global_permit, chain_permit))
+        })
+        .await;
+        drop(queued);
+        record_avg(&self.avg_wait_ms, wait_start.elapsed());
+
+        match acquired {
+            Ok(Some((global_permit, chain_permit))) => Ok(self.permit(global_permit, chain_permit)),
+            _ => Err(self.reject(chain_id)),
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            running: self.running.load(Ordering::SeqCst),
+            queued: self.queued.load(Ordering::SeqCst),
+            rejected: self.rejected.load(Ordering::SeqCst),
+            avg_wait_ms: self.avg_wait_ms.load(Ordering::SeqCst),
+            avg_run_ms: self.avg_run_ms.load(Ordering::SeqCst),
+            limits: self.limits.clone(),
+        }
+    }
+
+    fn try_acquire(&self, chain: Option<&Arc<Semaphore>>) -> Option<PoolPermit> {
+        let chain_permit = match chain {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+        let global_permit = self.global.clone().try_acquire_owned().ok()?;
+        Some(self.permit(global_permit, chain_permit))
+    }
+
+    fn permit(
+        &self,
+        global_permit: OwnedSemaphorePermit,
+        chain_permit: Option<OwnedSemaphorePermit>,
+    ) -> PoolPermit {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        PoolPermit {
+            _global: global_permit,
+            _chain: chain_permit,
+            running: self.running.clone(),
+            avg_run_ms: self.avg_run_ms.clone(),
+            started_at: Instant::now(),
+        }
+    }
+
+    fn chain_semaphore(&self, chain_id: u64) -> Arc<Semaphore> {
+        let mut per_chain = self.per_chain.lock().expect("pool lock poisoned");
+        // chain_id comes from the query string, forget chains nobody is
+        // running or waiting on so the map stays bounded by active chains
+        per_chain.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        per_chain
+            .entry(chain_id)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limits.max_per_chain)))
+            .clone()
+    }
+
+    fn reject(&self, chain_id: Option<u64>) -> MevlogError {
+        self.rejected.fetch_add(1, Ordering::SeqCst);
+        let stats = self.stats();
+        tracing::warn!(
+            "mevlog pool full for chain {:?}: {} running, {} queued",
+            chain_id,
+            stats.running,
+            stats.queued
+        );
+
+        // Rough time for the current queue to drain
+        let backlog = (stats.queued + 1) as u64;
+        let drain_ms = stats.avg_run_ms * backlog / self.limits.max_concurrent.max(1) as u64;
+        MevlogError::Busy {
+            retry_after: drain_ms.div_ceil(1000).max(1),
+        }
+    }
+}
+
+/// Counts a request as queued for as long as it's alive, so a waiter whose
+/// request was dropped mid-wait doesn't stay counted.
+struct QueuedGuard<'a> {
+    queued: &'a AtomicUsize,
+    /// Requests that were already queued when this one joined.
+    position: usize,
+}
+
+impl<'a> QueuedGuard<'a> {
+    fn new(queued: &'a AtomicUsize) -> Self {
+        let position = queued.fetch_add(1, Ordering::SeqCst);
+        Self { queued, position }
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn record_avg(avg_ms: &AtomicU64, sample: Duration) {
+    let sample = sample.as_millis() as u64;
+    let current = avg_ms.load(Ordering::SeqCst);
+    let updated = if current == 0 {
+        sample
+    } else {
+        (current * 7 + sample) / 8
+    };
+    avg_ms.store(updated, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_concurrent: usize, max_per_chain: usize, max_queued: usize) -> PoolLimits {
+        PoolLimits {
+            max_concurrent,
+            max_per_chain,
+            max_queued,
+            max_wait: Duration::from_millis(50),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_when_queue_is_full() {
+        let pool = MevlogPool::new(limits(1, 1, 0));
+        let _permit = pool.acquire(Some(1)).await.unwrap();
+
+        let err = pool.acquire(Some(1)).await.err().unwrap();
+        assert!(matches!(err, MevlogError::Busy { .. }));
+        assert_eq!(pool.stats().rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn limits_runs_per_chain() {
+        let pool = MevlogPool::new(limits(4, 1, 4));
+        let _permit = pool.acquire(Some(1)).await.unwrap();
+
+        assert!(pool.acquire(Some(1)).await.is_err());
+        assert!(pool.acquire(Some(10)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn queued_request_gets_released_slot() {
+        let pool = Arc::new(MevlogPool::new(PoolLimits {
+            max_wait: Duration::from_secs(1),
+            ..limits(1, 1, 1)
+        }));
+        let permit = pool.acquire(None).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire(None).await.ok() }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+
+        drop(permit);
+        let queued_permit = waiting.await.unwrap();
+        assert!(queued_permit.is_some());
+        assert_eq!(pool.stats().running, 1);
+    }
+
+    #[tokio::test]
+    async fn dropped_waiter_leaves_the_queue() {
+        let pool = Arc::new(MevlogPool::new(PoolLimits {
+            max_wait: Duration::from_secs(5),
+            ..limits(1, 1, 1)
+        }));
+        let _permit = pool.acquire(None).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire(None).await.ok() }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+
+        waiting.abort();
+        let _ = waiting.await;
+        assert_eq!(pool.stats().queued, 0);
+
+        // The freed queue spot is usable again
+        let pool_clone = pool.clone();
+        let waiting = tokio::spawn(async move { pool_clone.acquire(None).await.ok() });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+        assert_eq!(pool.stats().rejected, 0);
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn forgets_idle_chains() {
+        let pool = MevlogPool::new(limits(4, 1, 0));
+        let busy = pool.acquire(Some(1)).await.unwrap();
+
+        for chain_id in 100..1100 {
+            drop(pool.acquire(Some(chain_id)).await.unwrap());
+        }
+        {
+            let per_chain = pool.per_chain.lock().unwrap();
+            assert!(per_chain.len() <= 2);
+            assert!(per_chain.contains_key(&1));
+        }
+
+        // The busy chain keeps its limit
+        assert!(pool.acquire(Some(1)).await.is_err());
+        drop(busy);
+        assert!(pool.acquire(Some(1)).await.is_ok());
+    }
+}
@@ -1,3 +1,24 @@
+pub mod block_watcher;
+pub mod cache_warmer;
+pub mod custom_rpc;
+pub mod explore;
+pub mod explore_cache;
+pub mod file_table;
+pub mod fixture_runner;
+pub mod metrics;
+pub mod mevlog_cmd;
+pub mod mevlog_lib;
+pub mod mevlog_pool;
+pub mod price_history;
 pub mod prices;
+pub mod request_id;
+pub mod rpc_guard;
+pub mod rpc_pool;
+pub mod rpc_prober;
 pub mod rpc_utils;
+pub mod scheduler_status;
+pub mod search_query;
+pub mod search_stream;
+pub mod shutdown;
 pub mod utils;
+pub mod validation;
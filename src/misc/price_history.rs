@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::app_config::app_config;
+use crate::misc::{
+    file_table::FileTable,
+    prices::{fetch_history, get_native_price, native_symbol},
+    rpc_utils::fetch_block_timestamp,
+    search_query::BlockRange,
+    utils::unix_now,
+};
+
+const HOUR: u64 = 3600;
+const DAY: u64 = 24 * HOUR;
+/// Blocks younger than this are priced with the live price.
+const LIVE_PRICE_WINDOW: Duration = Duration::from_secs(HOUR);
+/// A price point further than this from a block isn't used for it.
+const MAX_POINT_DISTANCE: Duration = Duration::from_secs(36 * HOUR);
+/// History is kept hourly for this long, and thinned to daily beyond.
+const HOURLY_RETENTION: Duration = Duration::from_secs(90 * DAY);
+const MAX_BLOCK_TIMESTAMPS: usize = 10_000;
+
+/// Where the scheduler writes the price history for the server to read.
+pub fn price_history_path() -> PathBuf {
+    app_config().price_history.file.clone().into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PricePoint {
+    /// Unix timestamp, in seconds.
+    pub timestamp: u64,
+    pub usd: f64,
+}
+
+/// USD prices of native currencies over time, keyed by symbol. Points are
+/// sorted by timestamp.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceHistory {
+    pub symbols: HashMap<String, Vec<PricePoint>>,
+}
+
+impl PriceHistory {
+    /// The point closest to `timestamp`, `None` when there's none within
+    /// [`MAX_POINT_DISTANCE`].
+    pub fn closest(&self, symbol: &str, timestamp: u64) -> Option<PricePoint> {
+        let points = self.symbols.get(symbol)?;
+        let after = points.partition_point(|point| point.timestamp < timestamp);
+        let before = after.checked_sub(1).map(|before| points[before]);
+
+        [before, points.get(after).copied()]
+            .into_iter()
+            .flatten()
+            .min_by_key(|point| point.timestamp.abs_diff(timestamp))
+            .filter(|point| point.timestamp.abs_diff(timestamp) <= MAX_POINT_DISTANCE.as_secs())
+    }
+
+    pub fn last_timestamp(&self, symbol: &str) -> Option<u64> {
+        self.symbols
+            .get(symbol)?
+            .last()
+            .map(|point| point.timestamp)
+    }
+
+    /// Adds `points`, keeping one per hour within [`HOURLY_RETENTION`] of
+    /// `now` and one per day before.
+    pub fn merge(&mut self, symbol: &str, points: Vec<PricePoint>, now: u64) {
+        let hourly_since = now.saturating_sub(HOURLY_RETENTION.as_secs());
+        let slot = |point: &PricePoint| {
+            if point.timestamp >= hourly_since {
+                point.timestamp / HOUR
+            } else {
+                point.timestamp / DAY * DAY / HOUR
+            }
+        };
+
+        let history = self.symbols.entry(symbol.to_string()).or_default();
+        history.extend(points);
+        history.sort_by_key(|point| point.timestamp);
+        history.dedup_by(|later, earlier| slot(later) == slot(earlier));
+    }
+}
+
+/// Fetches what's missing of each symbol's history since its last point,
+/// or the configured backfill when there's none yet, and replaces the
+/// history file.
+pub async fn update_price_history(symbols: &[String]) -> Result<()> {
+    let path = price_history_path();
+    let mut history = match tokio::fs::read_to_string(&path).await {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+        Err(_) => PriceHistory::default(),
+    };
+
+    let now = unix_now();
+    for symbol in symbols {
+        let symbol = &symbol.to_uppercase();
+        let days = match history.last_timestamp(symbol) {
+            Some(last) => {
+                (now.saturating_sub(last) / DAY + 1).min(HOURLY_RETENTION.as_secs() / DAY)
+            }
+            None => app_config().price_history.backfill_days,
+        };
+
+        match fetch_history(symbol, days).await {
+            Ok(points) => {
+                tracing::info!(
+                    "Fetched {} price points of {} for the last {} days",
+                    points.len(),
+                    symbol,
+                    days
+                );
+                history.merge(symbol, points, now);
+            }
+            Err(e) => tracing::error!("Failed to update price history of {}: {}", symbol, &e),
+        }
+    }
+
+    // Written aside and renamed, so the server never reads a partial file
+    let tmp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec(&history)?).await?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    Ok(())
+}
+
+static PRICE_HISTORY: FileTable<PriceHistory> = FileTable::new(price_history_path);
+
+/// Price of `symbol` closest to `timestamp` from the scheduler's history.
+async fn historical_price(symbol: &str, timestamp: u64) -> Option<PricePoint> {
+    PRICE_HISTORY.get().await?.closest(symbol, timestamp)
+}
+
+static BLOCK_TIMESTAMPS: LazyLock<Mutex<HashMap<(u64, u64), u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+async fn block_timestamp(chain_id: u64, block_number: u64) -> Result<u64> {
+    let key = (chain_id, block_number);
+    if let Some(timestamp) = BLOCK_TIMESTAMPS
+        .lock()
+        .expect("block timestamps lock poisoned")
+        .get(&key)
+    {
+        return Ok(*timestamp);
+    }
+
+    let timestamp = fetch_block_timestamp(chain_id, block_number).await?;
+
+    let mut timestamps = BLOCK_TIMESTAMPS
+        .lock()
+        .expect("block timestamps lock poisoned");
+    if timestamps.len() >= MAX_BLOCK_TIMESTAMPS {
+        timestamps.clear();
+    }
+    timestamps.insert(key, timestamp);
+    Ok(timestamp)
+}
+
+/// The native token price a query was run with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriceQuote {
+    pub usd: f64,
+    /// Unix timestamp of the price, in seconds.
+    pub timestamp: u64,
+    /// Whether it came from the price history rather than the live price.
+    pub historical: bool,
+    /// Seconds since the live price was fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
+    /// The live price couldn't be refreshed for a while, see
+    /// [`crate::misc::prices::NativePrice::is_stale`].
+    pub stale: bool,
+}
+
+/// Native token price at the time of `block_number`: the live price for
+/// recent blocks or `None` (`latest`), the closest point of the price
+/// history for older ones. `None` when neither is known, so USD values
+/// are left out rather than computed with a price from another time.
+pub async fn price_for_block(chain_id: u64, block_number: Option<u64>) -> Option<PriceQuote> {
+    let block_timestamp = match block_number {
+        Some(block_number) => match block_timestamp(chain_id, block_number).await {
+            Ok(timestamp) => Some(timestamp),
+            Err(e) => {
+                tracing::warn!(
+                    "No timestamp of block {} on chain {} to price it: {}",
+                    block_number,
+                    chain_id,
+                    &e
+                );
+                return None;
+            }
+        },
+        None => None,
+    };
+
+    match block_timestamp {
+        Some(timestamp) if unix_now().saturating_sub(timestamp) > LIVE_PRICE_WINDOW.as_secs() => {
+            let symbol = native_symbol(chain_id).await.ok()?;
+            let point = historical_price(&symbol, timestamp).await?;
+            Some(PriceQuote {
+                usd: point.usd,
+                timestamp: point.timestamp,
+                historical: true,
+                age_secs: None,
+                stale: false,
+            })
+        }
+        _ => {
+            let price = get_native_price(chain_id).await.ok().flatten()?;
+            Some(PriceQuote {
+                usd: price.usd,
+                timestamp: price.timestamp,
+                historical: false,
+                age_secs: Some(price.age().as_secs()),
+                stale: price.is_stale(),
+            })
+        }
+    }
+}
+
+/// [`price_for_block`] of the last block of a `blocks` value, searches
+/// relative to `latest` get the live price.
+pub async fn price_for_blocks(chain_id: u64, blocks: &str) -> Option<PriceQuote> {
+    let last_block = if BlockRange::is_relative(blocks) {
+        None
+    } else {
+        Some(BlockRange::resolve(blocks, None)?.to)
+    };
+    price_for_block(chain_id, last_block).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: u64, usd: f64) -> PricePoint {
+        PricePoint { timestamp, usd }
+    }
+
+    #[test]
+    fn finds_closest_point_within_distance() {
+        let history = PriceHistory {
+            symbols: HashMap::from([(
+                "ETH".to_string(),
+                vec![
+                    point(DAY, 100.0),
+                    point(2 * DAY, 200.0),
+                    point(10 * DAY, 300.0),
+                ],
+            )]),
+        };
+
+        assert_eq!(history.closest("ETH", DAY + 100), Some(point(DAY, 100.0)));
+        assert_eq!(
+            history.closest("ETH", 2 * DAY - 100),
+            Some(point(2 * DAY, 200.0))
+        );
+        assert_eq!(history.closest("ETH", 0), Some(point(DAY, 100.0)));
+        assert_eq!(history.closest("ETH", 6 * DAY), None);
+        assert_eq!(history.closest("BNB", DAY), None);
+    }
+
+    #[test]
+    fn thins_old_points_to_daily() {
+        let now = 200 * DAY;
+        let old = now - HOURLY_RETENTION.as_secs() - 10 * DAY;
+        let recent = now - DAY;
+        let mut history = PriceHistory::default();
+
+        history.merge(
+            "ETH",
+            vec![
+                point(recent + 2 * HOUR, 4.0),
+                point(old, 1.0),
+                point(old + HOUR, 2.0),
+                point(recent, 3.0),
+                point(recent + 60, 3.5),
+            ],
+            now,
+        );
+
+        assert_eq!(
+            history.symbols["ETH"],
+            vec![
+                point(old, 1.0),
+                point(recent, 3.0),
+                point(recent + 2 * HOUR, 4.0)
+            ]
+        );
+        assert_eq!(history.last_timestamp("ETH"), Some(recent + 2 * HOUR));
+    }
+}
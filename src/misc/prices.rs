@@ -1,98 +1,836 @@
-use std::sync::LazyLock;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
 
 use eyre::{Result, bail};
+use futures::{FutureExt, future::BoxFuture};
 use reqwest;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-const COINGECKO_API_URL: &str =
-    "https://api.coingecko.com/api/v3/simple/price?ids=ethereum,binancecoin&vs_currencies=usd";
+use crate::{
+    config::app_config::app_config,
+    controllers::json::chain_info_controller::fetch_chain_info_no_rpcs,
+    misc::{metrics::METRICS, price_history::PricePoint, utils::unix_now},
+};
 
-static PRICE_CACHE: LazyLock<RwLock<Option<PriceResponse>>> = LazyLock::new(|| RwLock::new(None));
+const COINGECKO_API_URL: &str = "https://api.coingecko.com/api/v3/simple/price";
+const COINGECKO_CHART_URL: &str = "https://api.coingecko.com/api/v3/coins";
+const CRYPTOCOMPARE_API_URL: &str = "https://min-api.cryptocompare.com/data/pricemulti";
+const CRYPTOCOMPARE_HISTORY_URL: &str = "https://min-api.cryptocompare.com/data/v2";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TokenPrice {
-    #[serde(default)]
-    pub usd: Option<f64>,
+/// Prices younger than this are served without asking the sources again.
+const REFRESH_AFTER: Duration = Duration::from_secs(300);
+/// Prices older than this are still served, but marked stale.
+const MAX_PRICE_AGE: Duration = Duration::from_secs(900);
+/// How long past [`MAX_PRICE_AGE`] a stale price is served. After that it's
+/// not passed to mevlog and USD columns stay empty.
+const STALE_GRACE: Duration = Duration::from_secs(3600);
+/// Minimum time between two fetches of the same symbol, so a failing source
+/// isn't hit by every request.
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+/// A source answering 429 or 5xx is skipped for this long, doubling with
+/// every further such answer.
+const MIN_SOURCE_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_SOURCE_BACKOFF: Duration = Duration::from_secs(1800);
+/// Symbols refreshed by the scheduler even before anyone asked for them.
+const DEFAULT_SYMBOLS: [&str; 2] = ["ETH", "BNB"];
+
+/// CoinGecko ids of native currencies whose symbol alone is ambiguous there.
+const COINGECKO_IDS: [(&str, &str); 14] = [
+    ("ETH", "ethereum"),
+    ("BNB", "binancecoin"),
+    ("POL", "polygon-ecosystem-token"),
+    ("MATIC", "matic-network"),
+    ("AVAX", "avalanche-2"),
+    ("FTM", "fantom"),
+    ("S", "sonic-3"),
+    ("XDAI", "xdai"),
+    ("CELO", "celo"),
+    ("GLMR", "moonbeam"),
+    ("MNT", "mantle"),
+    ("CRO", "crypto-com-chain"),
+    ("KAIA", "kaia"),
+    ("BERA", "berachain-bera"),
+];
+
+/// A provider of native token USD prices, keyed by currency symbol.
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// USD prices of those `symbols` the source knows, others are left out.
+    fn fetch<'a>(&'a self, symbols: &'a [String]) -> BoxFuture<'a, Result<HashMap<String, f64>>>;
+
+    /// USD prices of `symbol` over the last `days`, oldest first. Empty
+    /// when the source keeps no history of it.
+    fn history<'a>(
+        &'a self,
+        _symbol: &'a str,
+        _days: u64,
+    ) -> BoxFuture<'a, Result<Vec<PricePoint>>> {
+        async { Ok(vec![]) }.boxed()
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PriceResponse {
-    pub ethereum: TokenPrice,
-    pub binancecoin: TokenPrice,
+pub type PriceSources = Arc<Vec<Arc<dyn PriceSource>>>;
+
+static PRICE_SOURCES: LazyLock<std::sync::RwLock<PriceSources>> = LazyLock::new(|| {
+    let mut sources: Vec<Arc<dyn PriceSource>> = vec![
+        Arc::new(CoinGeckoSource::default()),
+        Arc::new(CryptoCompareSource),
+    ];
+    if let Some(path) = &app_config().prices_file {
+        sources.push(Arc::new(StaticFileSource::new(path.clone())));
+    }
+    std::sync::RwLock::new(Arc::new(sources))
+});
+
+pub fn price_sources() -> PriceSources {
+    PRICE_SOURCES
+        .read()
+        .expect("price sources lock poisoned")
+        .clone()
 }
 
-pub async fn get_price_for_chain_id(chain_id: u64) -> Result<Option<f64>> {
-    match chain_id {
-        1 => Ok(get_crypto_prices().await?.ethereum.usd),
-        56 => Ok(get_crypto_prices().await?.binancecoin.usd),
-        _ => Ok(None),
+/// Replaces the sources asked for prices, in order of preference.
+pub fn set_price_sources(sources: Vec<Arc<dyn PriceSource>>) {
+    *PRICE_SOURCES.write().expect("price sources lock poisoned") = Arc::new(sources);
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NativePrice {
+    pub usd: f64,
+    pub source: &'static str,
+    /// Unix timestamp of the fetch, in seconds.
+    pub timestamp: u64,
+    #[serde(skip)]
+    pub fetched_at: Instant,
+}
+
+impl NativePrice {
+    pub fn age(&self) -> Duration {
+        self.fetched_at.elapsed()
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.age() < REFRESH_AFTER
+    }
+
+    /// Older than [`MAX_PRICE_AGE`], the sources failed to refresh it.
+    pub fn is_stale(&self) -> bool {
+        self.age() >= MAX_PRICE_AGE
+    }
+
+    /// Whether the price is recent enough to be passed to mevlog.
+    fn is_usable(&self) -> bool {
+        self.age() < MAX_PRICE_AGE + STALE_GRACE
     }
 }
 
-pub async fn get_crypto_prices() -> Result<PriceResponse> {
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceStats {
+    pub symbol: String,
+    pub usd: f64,
+    pub source: &'static str,
+    pub timestamp: u64,
+    pub age_secs: u64,
+    pub stale: bool,
+    /// Too old to be used anymore.
+    pub expired: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceSourceStats {
+    pub name: &'static str,
+    pub failures: u32,
+    pub backoff_for_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Cached prices and how each source has been doing, for `/api/status`.
+pub async fn price_stats() -> (Vec<PriceStats>, Vec<PriceSourceStats>) {
+    let mut prices: Vec<PriceStats> = PRICE_CACHE
+        .read()
+        .await
+        .prices
+        .iter()
+        .map(|(symbol, price)| PriceStats {
+            symbol: symbol.clone(),
+            usd: price.usd,
+            source: price.source,
+            timestamp: price.timestamp,
+            age_secs: price.age().as_secs(),
+            stale: price.is_stale(),
+            expired: !price.is_usable(),
+        })
+        .collect();
+    prices.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+    let now = Instant::now();
+    let health = SOURCE_HEALTH
+        .lock()
+        .expect("price sources health lock poisoned");
+    let sources = price_sources()
+        .iter()
+        .map(|source| {
+            let health = health.get(source.name());
+            PriceSourceStats {
+                name: source.name(),
+                failures: health.map_or(0, |health| health.failures),
+                backoff_for_secs: health
+                    .and_then(|health| health.backoff_until)
+                    .filter(|until| *until > now)
+                    .map(|until| (until - now).as_secs()),
+                last_error: health.and_then(|health| health.last_error.clone()),
+            }
+        })
+        .collect();
+
+    (prices, sources)
+}
+
+/// Rate limiting and outages of a source, see [`MIN_SOURCE_BACKOFF`].
+#[derive(Debug, Default)]
+struct SourceHealth {
+    /// 429 and 5xx answers in a row.
+    failures: u32,
+    backoff_until: Option<Instant>,
+    last_error: Option<String>,
+}
+
+static SOURCE_HEALTH: LazyLock<Mutex<HashMap<&'static str, SourceHealth>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn is_backing_off(source: &dyn PriceSource) -> bool {
+    SOURCE_HEALTH
+        .lock()
+        .expect("price sources health lock poisoned")
+        .get(source.name())
+        .and_then(|health| health.backoff_until)
+        .is_some_and(|until| until > Instant::now())
+}
+
+fn record_source_result<T>(source: &dyn PriceSource, result: &Result<T>) {
+    let mut health = SOURCE_HEALTH
+        .lock()
+        .expect("price sources health lock poisoned");
+    let health = health.entry(source.name()).or_default();
+
+    let e = match result {
+        Ok(_) => {
+            health.failures = 0;
+            health.backoff_until = None;
+            return;
+        }
+        Err(e) => e,
+    };
+    health.last_error = Some(e.to_string());
+
+    if let Some(e) = e.downcast_ref::<PriceApiError>()
+        && e.should_back_off()
     {
+        health.failures += 1;
+        let backoff = backoff_delay(health.failures, e.retry_after);
+        health.backoff_until = Some(Instant::now() + backoff);
+        tracing::warn!(
+            "Price source {} answered {}, backing off for {:?}",
+            source.name(),
+            e.status,
+            backoff
+        );
+    }
+}
+
+/// Doubles with each failure in a row, but never shorter than what the
+/// API asked for in `Retry-After`.
+fn backoff_delay(failures: u32, retry_after: Option<Duration>) -> Duration {
+    let backoff = MIN_SOURCE_BACKOFF * 2u32.saturating_pow(failures.saturating_sub(1));
+    backoff
+        .min(MAX_SOURCE_BACKOFF)
+        .max(retry_after.unwrap_or_default().min(MAX_SOURCE_BACKOFF))
+}
+
+#[derive(Debug, Default)]
+struct PriceCache {
+    prices: HashMap<String, NativePrice>,
+    last_attempt: HashMap<String, Instant>,
+    /// Native currency symbol per chain, it doesn't change.
+    chain_symbols: HashMap<u64, String>,
+}
+
+static PRICE_CACHE: LazyLock<RwLock<PriceCache>> =
+    LazyLock::new(|| RwLock::new(PriceCache::default()));
+
+/// USD price of the chain's native token, `None` when no source knows it
+/// or the last known price is past its [`STALE_GRACE`].
+pub async fn get_price_for_chain_id(chain_id: u64) -> Result<Option<f64>> {
+    Ok(get_native_price(chain_id).await?.map(|price| price.usd))
+}
+
+/// Like [`get_price_for_chain_id`], along with when and where the price
+/// was fetched.
+pub async fn get_native_price(chain_id: u64) -> Result<Option<NativePrice>> {
+    let symbol = native_symbol(chain_id).await?;
+    Ok(get_price(&symbol).await)
+}
+
+/// Price of a native currency symbol, refreshed from the sources when it's
+/// older than [`REFRESH_AFTER`].
+pub async fn get_price(symbol: &str) -> Option<NativePrice> {
+    let symbol = symbol.to_uppercase();
+    let (cached, should_fetch) = {
         let cache = PRICE_CACHE.read().await;
-        if let Some(cached_prices) = cache.as_ref() {
-            return Ok(cached_prices.clone());
-        }
+        let cached = cache.prices.get(&symbol).cloned();
+        let fresh = cached.as_ref().is_some_and(NativePrice::is_fresh);
+        let recently_tried = cache
+            .last_attempt
+            .get(&symbol)
+            .is_some_and(|at| at.elapsed() < RETRY_AFTER);
+        (cached, !fresh && !recently_tried)
+    };
+    METRICS.record_cache_lookup("prices", !should_fetch);
+
+    let price = if should_fetch {
+        refresh_prices(std::slice::from_ref(&symbol)).await;
+        PRICE_CACHE.read().await.prices.get(&symbol).cloned()
+    } else {
+        cached
+    };
+
+    price.filter(NativePrice::is_usable)
+}
+
+/// Native currency symbol of the chain, as chain-info reports it.
+pub async fn native_symbol(chain_id: u64) -> Result<String> {
+    if let Some(symbol) = PRICE_CACHE.read().await.chain_symbols.get(&chain_id) {
+        return Ok(symbol.clone());
     }
 
-    let prices = fetch_prices_from_api().await?;
+    let chain_info = fetch_chain_info_no_rpcs(chain_id).await?;
+    let symbol = chain_info.currency.to_uppercase();
+    PRICE_CACHE
+        .write()
+        .await
+        .chain_symbols
+        .insert(chain_id, symbol.clone());
+    Ok(symbol)
+}
 
+/// Asks the sources in order, each one only for the symbols the previous
+/// ones didn't return.
+async fn refresh_prices(symbols: &[String]) {
     {
         let mut cache = PRICE_CACHE.write().await;
-        tracing::debug!("Prices cache updated: {:?}", prices);
-        *cache = Some(prices.clone());
+        for symbol in symbols {
+            cache.last_attempt.insert(symbol.clone(), Instant::now());
+        }
+    }
+
+    let prices = fetch_from_sources(&price_sources(), symbols).await;
+    let missing: Vec<&String> = symbols
+        .iter()
+        .filter(|symbol| !prices.contains_key(*symbol))
+        .collect();
+    if !missing.is_empty() {
+        tracing::warn!("No price source knows {:?}", missing);
     }
 
-    Ok(prices)
+    PRICE_CACHE.write().await.prices.extend(prices);
 }
 
+async fn fetch_from_sources(
+    sources: &[Arc<dyn PriceSource>],
+    symbols: &[String],
+) -> HashMap<String, NativePrice> {
+    let mut prices = HashMap::new();
+    let mut missing: Vec<String> = symbols.to_vec();
+
+    for source in sources {
+        if missing.is_empty() {
+            break;
+        }
+        if is_backing_off(source.as_ref()) {
+            continue;
+        }
+        let result = source.fetch(&missing).await;
+        record_source_result(source.as_ref(), &result);
+        let fetched = match result {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                tracing::error!("Price source {} failed: {}", source.name(), &e);
+                continue;
+            }
+        };
+
+        for (symbol, usd) in fetched {
+            missing.retain(|missing| *missing != symbol);
+            prices.insert(
+                symbol,
+                NativePrice {
+                    usd,
+                    source: source.name(),
+                    timestamp: unix_now(),
+                    fetched_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    prices
+}
+
+/// History of `symbol` from the first source that has one.
+pub async fn fetch_history(symbol: &str, days: u64) -> Result<Vec<PricePoint>> {
+    for source in price_sources().iter() {
+        if is_backing_off(source.as_ref()) {
+            continue;
+        }
+        let result = source.history(symbol, days).await;
+        record_source_result(source.as_ref(), &result);
+        match result {
+            Ok(points) if !points.is_empty() => return Ok(points),
+            Ok(_) => {}
+            Err(e) => tracing::error!("Price source {} history failed: {}", source.name(), &e),
+        }
+    }
+    bail!("No price source has history of {}", symbol)
+}
+
+/// Refreshes every symbol asked for so far, plus [`DEFAULT_SYMBOLS`].
 pub async fn update_prices_cache() -> Result<()> {
-    let prices = fetch_prices_from_api().await?;
-    {
-        let mut cache = PRICE_CACHE.write().await;
-        tracing::debug!("Prices cache updated: {:?}", prices);
-        *cache = Some(prices);
+    let mut symbols: Vec<String> = PRICE_CACHE.read().await.prices.keys().cloned().collect();
+    for symbol in DEFAULT_SYMBOLS {
+        if !symbols.iter().any(|known| known == symbol) {
+            symbols.push(symbol.to_string());
+        }
     }
 
+    refresh_prices(&symbols).await;
+
+    let cache = PRICE_CACHE.read().await;
+    tracing::debug!("Prices cache updated: {:?}", cache.prices);
+    let refreshed = symbols
+        .iter()
+        .any(|symbol| cache.prices.get(symbol).is_some_and(NativePrice::is_fresh));
+    if !refreshed {
+        bail!("No prices could be fetched for {:?}", symbols);
+    }
     Ok(())
 }
 
-async fn fetch_prices_from_api() -> Result<PriceResponse> {
+/// An error status from a price API.
+#[derive(Debug)]
+pub struct PriceApiError {
+    pub status: reqwest::StatusCode,
+    pub retry_after: Option<Duration>,
+    pub body: String,
+}
+
+impl PriceApiError {
+    fn should_back_off(&self) -> bool {
+        self.status == reqwest::StatusCode::TOO_MANY_REQUESTS || self.status.is_server_error()
+    }
+}
+
+impl std::fmt::Display for PriceApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Price API returned status {}: {}",
+            self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for PriceApiError {}
+
+async fn get_json<T: for<'de> Deserialize<'de>>(url: &str, query: &[(&str, String)]) -> Result<T> {
     let client = reqwest::Client::new();
     let response = match client
-        .get(COINGECKO_API_URL)
+        .get(url)
+        .query(query)
         .header("User-Agent", "mevlog-backend/1.0")
+        .timeout(Duration::from_secs(5))
         .send()
         .await
     {
         Ok(response) => response,
         Err(e) => {
-            let msg = format!("Failed to fetch prices from API: {}", &e);
+            let msg = format!("Failed to fetch prices from {}: {}", url, &e);
             tracing::error!("{}", &msg);
             bail!("{}", &msg)
         }
     };
 
     let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs);
     let body = response.text().await?;
 
     if !status.is_success() {
-        tracing::error!("CoinGecko API error ({}): {}", status, &body);
-        bail!("CoinGecko API returned status {}: {}", status, &body);
+        tracing::error!("Price API error ({}): {}", status, &body);
+        return Err(PriceApiError {
+            status,
+            retry_after,
+            body,
+        }
+        .into());
     }
 
-    match serde_json::from_str::<PriceResponse>(&body) {
+    match serde_json::from_str::<T>(&body) {
         Ok(prices) => Ok(prices),
         Err(e) => {
-            tracing::error!(
-                "Failed to parse CoinGecko response: {} | Body: {}",
-                &e,
-                &body
-            );
+            tracing::error!("Failed to parse price response: {} | Body: {}", &e, &body);
             bail!("Failed to parse prices: {}", e)
         }
     }
 }
+
+/// Prices from CoinGecko. Symbols missing from [`COINGECKO_IDS`] are
+/// looked up by symbol, which CoinGecko resolves to the largest coin.
+pub struct CoinGeckoSource {
+    ids: HashMap<String, String>,
+}
+
+impl Default for CoinGeckoSource {
+    fn default() -> Self {
+        Self {
+            ids: COINGECKO_IDS
+                .iter()
+                .map(|(symbol, id)| (symbol.to_string(), id.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinGeckoPrice {
+    #[serde(default)]
+    usd: Option<f64>,
+}
+
+impl PriceSource for CoinGeckoSource {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    fn fetch<'a>(&'a self, symbols: &'a [String]) -> BoxFuture<'a, Result<HashMap<String, f64>>> {
+        async move {
+            let (known, unknown): (Vec<&String>, Vec<&String>) = symbols
+                .iter()
+                .partition(|symbol| self.ids.contains_key(*symbol));
+            let mut prices = HashMap::new();
+
+            if !known.is_empty() {
+                let ids: Vec<&str> = known
+                    .iter()
+                    .map(|symbol| self.ids[*symbol].as_str())
+                    .collect();
+                let response: HashMap<String, CoinGeckoPrice> = get_json(
+                    COINGECKO_API_URL,
+                    &[("ids", ids.join(",")), ("vs_currencies", "usd".to_string())],
+                )
+                .await?;
+                for symbol in known {
+                    if let Some(usd) = response.get(&self.ids[symbol]).and_then(|price| price.usd) {
+                        prices.insert(symbol.clone(), usd);
+                    }
+                }
+            }
+
+            if !unknown.is_empty() {
+                let lowercase: Vec<String> = unknown.iter().map(|s| s.to_lowercase()).collect();
+                let response: HashMap<String, CoinGeckoPrice> = get_json(
+                    COINGECKO_API_URL,
+                    &[
+                        ("symbols", lowercase.join(",")),
+                        ("vs_currencies", "usd".to_string()),
+                    ],
+                )
+                .await?;
+                for (symbol, price) in response {
+                    if let Some(usd) = price.usd {
+                        prices.insert(symbol.to_uppercase(), usd);
+                    }
+                }
+            }
+
+            Ok(prices)
+        }
+        .boxed()
+    }
+
+    fn history<'a>(&'a self, symbol: &'a str, days: u64) -> BoxFuture<'a, Result<Vec<PricePoint>>> {
+        async move {
+            let Some(id) = self.ids.get(symbol) else {
+                return Ok(vec![]);
+            };
+            let chart: CoinGeckoChart = get_json(
+                &format!("{COINGECKO_CHART_URL}/{id}/market_chart"),
+                &[
+                    ("vs_currency", "usd".to_string()),
+                    ("days", days.to_string()),
+                ],
+            )
+            .await?;
+            Ok(chart
+                .prices
+                .into_iter()
+                .map(|(timestamp_ms, usd)| PricePoint {
+                    timestamp: timestamp_ms as u64 / 1000,
+                    usd,
+                })
+                .collect())
+        }
+        .boxed()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CoinGeckoChart {
+    /// `[unix timestamp in ms, usd]` pairs.
+    prices: Vec<(f64, f64)>,
+}
+
+/// Prices from CryptoCompare, asked when CoinGecko fails or lacks a symbol.
+pub struct CryptoCompareSource;
+
+impl PriceSource for CryptoCompareSource {
+    fn name(&self) -> &'static str {
+        "cryptocompare"
+    }
+
+    fn fetch<'a>(&'a self, symbols: &'a [String]) -> BoxFuture<'a, Result<HashMap<String, f64>>> {
+        async move {
+            let response: HashMap<String, HashMap<String, f64>> = get_json(
+                CRYPTOCOMPARE_API_URL,
+                &[("fsyms", symbols.join(",")), ("tsyms", "USD".to_string())],
+            )
+            .await?;
+            Ok(response
+                .into_iter()
+                .filter_map(|(symbol, prices)| Some((symbol.to_uppercase(), *prices.get("USD")?)))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn history<'a>(&'a self, symbol: &'a str, days: u64) -> BoxFuture<'a, Result<Vec<PricePoint>>> {
+        async move {
+            // Hourly candles for short ranges, daily ones for backfills
+            let (endpoint, limit) = if days <= 7 {
+                ("histohour", days * 24)
+            } else {
+                ("histoday", days)
+            };
+            let response: CryptoCompareHistory = get_json(
+                &format!("{CRYPTOCOMPARE_HISTORY_URL}/{endpoint}"),
+                &[
+                    ("fsym", symbol.to_string()),
+                    ("tsym", "USD".to_string()),
+                    ("limit", limit.to_string()),
+                ],
+            )
+            .await?;
+            Ok(response
+                .data
+                .data
+                .into_iter()
+                // Candles before the symbol was listed are all zero
+                .filter(|candle| candle.close > 0.0)
+                .map(|candle| PricePoint {
+                    timestamp: candle.time,
+                    usd: candle.close,
+                })
+                .collect())
+        }
+        .boxed()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CryptoCompareHistory {
+    #[serde(rename = "Data")]
+    data: CryptoCompareCandles,
+}
+
+#[derive(Debug, Deserialize)]
+struct CryptoCompareCandles {
+    #[serde(rename = "Data")]
+    data: Vec<CryptoCompareCandle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CryptoCompareCandle {
+    time: u64,
+    close: f64,
+}
+
+/// Prices read from a JSON file of `{"SYMBOL": usd}`, for offline runs and
+/// tests. Set `PRICES_FILE` to use it as the last fallback.
+pub struct StaticFileSource {
+    path: PathBuf,
+}
+
+impl StaticFileSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl PriceSource for StaticFileSource {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn fetch<'a>(&'a self, symbols: &'a [String]) -> BoxFuture<'a, Result<HashMap<String, f64>>> {
+        async move {
+            let contents = tokio::fs::read_to_string(&self.path).await?;
+            let prices: HashMap<String, f64> = serde_json::from_str(&contents)?;
+            Ok(prices
+                .into_iter()
+                .map(|(symbol, usd)| (symbol.to_uppercase(), usd))
+                .filter(|(symbol, _)| symbols.contains(symbol))
+                .collect())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedSource(&'static str, Vec<(&'static str, f64)>);
+
+    impl PriceSource for FixedSource {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn fetch<'a>(
+            &'a self,
+            symbols: &'a [String],
+        ) -> BoxFuture<'a, Result<HashMap<String, f64>>> {
+            async move {
+                Ok(self
+                    .1
+                    .iter()
+                    .filter(|(symbol, _)| symbols.iter().any(|s| s == symbol))
+                    .map(|(symbol, usd)| (symbol.to_string(), *usd))
+                    .collect())
+            }
+            .boxed()
+        }
+    }
+
+    struct FailingSource;
+
+    impl PriceSource for FailingSource {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn fetch<'a>(&'a self, _: &'a [String]) -> BoxFuture<'a, Result<HashMap<String, f64>>> {
+            async move { bail!("429 Too Many Requests") }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_next_source_per_symbol() {
+        let sources: Vec<Arc<dyn PriceSource>> = vec![
+            Arc::new(FailingSource),
+            Arc::new(FixedSource("primary", vec![("ETH", 2.5)])),
+            Arc::new(FixedSource("fallback", vec![("ETH", 9.0), ("POL", 0.3)])),
+        ];
+        let symbols = ["ETH", "POL", "XYZ"].map(String::from);
+
+        let prices = fetch_from_sources(&sources, &symbols).await;
+        assert_eq!((prices["ETH"].usd, prices["ETH"].source), (2.5, "primary"));
+        assert_eq!((prices["POL"].usd, prices["POL"].source), (0.3, "fallback"));
+        assert!(!prices.contains_key("XYZ"));
+    }
+
+    struct RateLimitedSource(Mutex<u32>);
+
+    impl PriceSource for RateLimitedSource {
+        fn name(&self) -> &'static str {
+            "rate-limited"
+        }
+
+        fn fetch<'a>(&'a self, _: &'a [String]) -> BoxFuture<'a, Result<HashMap<String, f64>>> {
+            *self.0.lock().unwrap() += 1;
+            async move {
+                Err(PriceApiError {
+                    status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+                    retry_after: None,
+                    body: String::new(),
+                }
+                .into())
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn backs_off_rate_limited_source() {
+        let rate_limited = Arc::new(RateLimitedSource(Mutex::new(0)));
+        let sources: Vec<Arc<dyn PriceSource>> = vec![
+            rate_limited.clone(),
+            Arc::new(FixedSource("fallback", vec![("ETH", 2.0)])),
+        ];
+        let symbols = ["ETH".to_string()];
+
+        for _ in 0..3 {
+            let prices = fetch_from_sources(&sources, &symbols).await;
+            assert_eq!(prices["ETH"].source, "fallback");
+        }
+        assert_eq!(*rate_limited.0.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_and_honors_retry_after() {
+        assert_eq!(backoff_delay(1, None), MIN_SOURCE_BACKOFF);
+        assert_eq!(backoff_delay(3, None), MIN_SOURCE_BACKOFF * 4);
+        assert_eq!(backoff_delay(30, None), MAX_SOURCE_BACKOFF);
+        assert_eq!(
+            backoff_delay(1, Some(Duration::from_secs(120))),
+            Duration::from_secs(120)
+        );
+    }
+
+    #[test]
+    fn old_prices_are_not_used() {
+        let price = |age: Duration| NativePrice {
+            usd: 1.0,
+            source: "test",
+            timestamp: 0,
+            fetched_at: Instant::now() - age,
+        };
+
+        assert!(price(Duration::ZERO).is_fresh());
+        assert!(!price(REFRESH_AFTER).is_fresh());
+        assert!(!price(REFRESH_AFTER).is_stale());
+        assert!(price(MAX_PRICE_AGE).is_stale());
+        assert!(price(MAX_PRICE_AGE).is_usable());
+        assert!(!price(MAX_PRICE_AGE + STALE_GRACE).is_usable());
+    }
+
+    #[tokio::test]
+    async fn reads_static_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("prices-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"eth": 2500.5, "BNB": 600.0}"#)?;
+
+        let source = StaticFileSource::new(&path);
+        let prices = source.fetch(&["ETH".to_string()]).await?;
+        assert_eq!(prices, HashMap::from([("ETH".to_string(), 2500.5)]));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
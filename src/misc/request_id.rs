@@ -0,0 +1,120 @@
+use std::future::Future;
+
+use axum::http::HeaderMap;
+use serde_json::Value;
+use tracing::Span;
+use tracing_futures::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, `None` outside of one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Reuses the caller's `X-Request-Id` when it's safe to log and echo back,
+/// so a request can be followed across proxies.
+pub fn request_id_from(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map_or_else(new_request_id, str::to_string)
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn new_request_id() -> String {
+    Uuid::new_v4().simple().to_string()[0..12].to_string()
+}
+
+/// Runs `future` as part of request `id`.
+pub async fn scope_request_id<F: Future>(id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(id, future).await
+}
+
+/// Adds the current request id to an error JSON object.
+pub fn with_request_id(mut error: Value) -> Value {
+    if let (Value::Object(fields), Some(id)) = (&mut error, current_request_id()) {
+        fields.insert("request_id".to_string(), Value::from(id));
+    }
+    error
+}
+
+/// The request id and tracing span of the current request, carried into
+/// work that outlives the handler, like spawned searches and WebSocket
+/// sessions.
+#[derive(Debug, Clone)]
+pub struct RequestScope {
+    id: Option<String>,
+    span: Span,
+}
+
+impl RequestScope {
+    pub fn current() -> Self {
+        Self {
+            id: current_request_id(),
+            span: Span::current(),
+        }
+    }
+
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        let future = future.instrument(self.span);
+        match self.id {
+            Some(id) => REQUEST_ID.scope(id, future).await,
+            None => future.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn reuses_valid_incoming_ids() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("lb-4f2a.9_x"));
+        assert_eq!(request_id_from(&headers), "lb-4f2a.9_x");
+
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("a b\"c"));
+        let generated = request_id_from(&headers);
+        assert_eq!(generated.len(), 12);
+        assert_ne!(generated, "a b\"c");
+
+        headers.insert(
+            REQUEST_ID_HEADER,
+            HeaderValue::from_str(&"a".repeat(65)).unwrap(),
+        );
+        assert_eq!(request_id_from(&headers).len(), 12);
+    }
+
+    #[tokio::test]
+    async fn adds_id_to_errors_inside_a_request() {
+        let error = serde_json::json!({ "error": "boom" });
+        assert_eq!(with_request_id(error.clone()), error);
+
+        let error = scope_request_id("abc".to_string(), async {
+            RequestScope::current()
+                .run(async { with_request_id(serde_json::json!({ "error": "boom" })) })
+                .await
+        })
+        .await;
+        assert_eq!(
+            error,
+            serde_json::json!({ "error": "boom", "request_id": "abc" })
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use alloy::transports::http::reqwest;
+use eyre::{Result, bail, eyre};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, copy_bidirectional};
+use tokio::net::{TcpListener, TcpStream, lookup_host};
+use tokio::time::timeout;
+
+use crate::misc::custom_rpc::{RPC_POLICY, is_private_ip};
+
+const MAX_HEAD_LEN: usize = 8 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static PROXY_URL: LazyLock<Result<String, String>> = LazyLock::new(|| {
+    let start = || -> std::io::Result<String> {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(serve(TcpListener::from_std(listener)?));
+        Ok(format!("http://{addr}"))
+    };
+    start().map_err(|e| format!("Failed to start the RPC guard: {e}"))
+});
+
+/// Local HTTP proxy mevlog processes reach RPCs through while private
+/// addresses are blocked. Hosts are resolved and checked as each
+/// connection is made, so a name that passed the check when the RPC was
+/// verified can't be rebound to a private address for the run, and
+/// redirects to private addresses fail too. Started on first use.
+pub fn proxy_url() -> Result<&'static str, String> {
+    PROXY_URL.as_deref().map_err(Clone::clone)
+}
+
+/// Client for in-process calls to RPCs that aren't the operator's own. It
+/// doesn't follow redirects and goes through the guard while private
+/// addresses are blocked.
+pub fn guarded_client() -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    if RPC_POLICY.block_private_ips {
+        let proxy = proxy_url().map_err(|e| eyre!(e))?;
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    Ok(builder.build()?)
+}
+
+pub async fn serve(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = handle(stream).await {
+                        tracing::info!("RPC guard closed a connection: {}", &e);
+                    }
+                });
+            }
+            Err(e) => {
+                tracing::error!("RPC guard failed to accept: {}", &e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// Serves one proxied connection, a `CONNECT` tunnel for HTTPS or a
+/// forwarded plain HTTP request.
+async fn handle(mut client: TcpStream) -> Result<()> {
+    let request = read_head(&mut client).await?;
+    let head = String::from_utf8_lossy(&request);
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let (method, target) = (
+        request_line.next().unwrap_or_default(),
+        request_line.next().unwrap_or_default(),
+    );
+
+    let tunnel = method == "CONNECT";
+    let authority = if tunnel {
+        target.to_string()
+    } else {
+        let url = url::Url::parse(target).map_err(|_| eyre!("Invalid target '{target}'"))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| eyre!("No host in '{target}'"))?;
+        format!("{}:{}", host, url.port_or_known_default().unwrap_or(80))
+    };
+
+    let addrs = match checked_addrs(&authority).await {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            client
+                .write_all(
+                    b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+            return Err(e);
+        }
+    };
+    let mut upstream = timeout(CONNECT_TIMEOUT, TcpStream::connect(&addrs[..]))
+        .await
+        .map_err(|_| eyre!("Timed out connecting to {authority}"))??;
+
+    if tunnel {
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await?;
+    } else {
+        upstream.write_all(&request).await?;
+    }
+    copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Reads up to the end of the request head. Plain HTTP requests are
+/// forwarded as read, including any part of the body that came with it.
+async fn read_head(client: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut chunk = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_HEAD_LEN {
+            bail!("Request head too long");
+        }
+        let read = client.read(&mut chunk).await?;
+        if read == 0 {
+            bail!("Connection closed before the request head");
+        }
+        request.extend_from_slice(&chunk[..read]);
+    }
+    Ok(request)
+}
+
+async fn checked_addrs(authority: &str) -> Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = lookup_host(authority).await?.collect();
+    if addrs.is_empty() {
+        bail!("{authority} did not resolve");
+    }
+    if addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+        bail!("{authority} resolves to a private address");
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_private_addresses() -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(serve(listener));
+
+        for request in [
+            "CONNECT 169.254.169.254:443 HTTP/1.1\r\nHost: 169.254.169.254:443\r\n\r\n",
+            "CONNECT localhost:443 HTTP/1.1\r\nHost: localhost:443\r\n\r\n",
+            "GET http://10.0.0.5:8545/ HTTP/1.1\r\nHost: 10.0.0.5:8545\r\n\r\n",
+        ] {
+            let mut stream = TcpStream::connect(addr).await?;
+            stream.write_all(request.as_bytes()).await?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await?;
+            assert!(
+                response.starts_with("HTTP/1.1 403"),
+                "{request}: {response}"
+            );
+        }
+        Ok(())
+    }
+}
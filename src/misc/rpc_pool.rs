@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+use serde::Serialize;
+
+use crate::misc::mevlog_cmd::{MevlogCmd, MevlogError};
+
+pub static RPC_POOL: LazyLock<RpcPool> = LazyLock::new(RpcPool::default);
+
+/// Consecutive failures after which an endpoint is taken out of rotation.
+const QUARANTINE_AFTER: u32 = 3;
+const MIN_QUARANTINE: Duration = Duration::from_secs(30);
+const MAX_QUARANTINE: Duration = Duration::from_secs(600);
+/// How long a re-probed endpoint is kept from other requests while the
+/// probe is running.
+const PROBE_GRACE: Duration = Duration::from_secs(15);
+/// Weight of the newest observation in the success and latency averages.
+const EWMA_ALPHA: f64 = 0.2;
+/// Latency at which an endpoint's weight is halved.
+const LATENCY_HALF_WEIGHT_MS: f64 = 500.0;
+
+#[derive(Debug, Clone)]
+struct RpcHealth {
+    success_ewma: f64,
+    latency_ewma_ms: Option<f64>,
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    quarantine_trips: u32,
+    quarantined_until: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl Default for RpcHealth {
+    fn default() -> Self {
+        Self {
+            // New endpoints start trusted, they drop out fast enough if not
+            success_ewma: 1.0,
+            latency_ewma_ms: None,
+            successes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            quarantine_trips: 0,
+            quarantined_until: None,
+            last_error: None,
+        }
+    }
+}
+
+impl RpcHealth {
+    fn weight(&self) -> f64 {
+        let latency_penalty = self
+            .latency_ewma_ms
+            .map_or(1.0, |latency| 1.0 + latency / LATENCY_HALF_WEIGHT_MS);
+        // Never zero, so a weighted pick always has a candidate
+        (self.success_ewma.powi(2) / latency_penalty).max(f64::EPSILON)
+    }
+
+    fn is_quarantined(&self, now: Instant) -> bool {
+        self.quarantined_until.is_some_and(|until| until > now)
+    }
+
+    fn record_success(&mut self, latency: Option<Duration>) {
+        self.successes += 1;
+        self.success_ewma = ewma(self.success_ewma, 1.0);
+        if let Some(latency) = latency {
+            let latency_ms = latency.as_secs_f64() * 1000.0;
+            self.latency_ewma_ms = Some(match self.latency_ewma_ms {
+                Some(previous) => ewma(previous, latency_ms),
+                None => latency_ms,
+            });
+        }
+        self.consecutive_failures = 0;
+        self.quarantine_trips = 0;
+        self.quarantined_until = None;
+    }
+
+    fn record_failure(&mut self, error: &str, now: Instant) {
+        self.failures += 1;
+        self.success_ewma = ewma(self.success_ewma, 0.0);
+        self.consecutive_failures += 1;
+        self.last_error = Some(error.to_string());
+
+        if self.consecutive_failures >= QUARANTINE_AFTER {
+            let backoff = MIN_QUARANTINE * 2u32.saturating_pow(self.quarantine_trips);
+            self.quarantined_until = Some(now + backoff.min(MAX_QUARANTINE));
+            self.quarantine_trips += 1;
+        }
+    }
+}
+
+fn ewma(previous: f64, value: f64) -> f64 {
+    previous * (1.0 - EWMA_ALPHA) + value * EWMA_ALPHA
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcHealthStats {
+    pub url: String,
+    pub success_rate: f64,
+    pub latency_ms: Option<u64>,
+    pub successes: u64,
+    pub failures: u64,
+    pub quarantined_for_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Tracks how each chain's RPC endpoints have been doing and picks among
+/// them weighted by success rate and latency. Endpoints failing
+/// [`QUARANTINE_AFTER`] times in a row are skipped for a growing period,
+/// then let through once to probe whether they recovered.
+#[derive(Default)]
+pub struct RpcPool {
+    health: Mutex<HashMap<u64, HashMap<String, RpcHealth>>>,
+}
+
+impl RpcPool {
+    /// Picks one of `urls` for `chain_id`. Only quarantined endpoints are
+    /// skipped, when all of them are the one released soonest is used.
+    pub fn pick(&self, chain_id: u64, urls: &[String]) -> Option<String> {
+        self.select(chain_id, urls, &[], true)
+    }
+
+    /// The best scored of `urls` not in `tried`, for failing over after an
+    /// attempt against each of them failed.
+    pub fn pick_next(&self, chain_id: u64, urls: &[String], tried: &[String]) -> Option<String> {
+        self.select(chain_id, urls, tried, false)
+    }
+
+    fn select(
+        &self,
+        chain_id: u64,
+        urls: &[String],
+        tried: &[String],
+        weighted: bool,
+    ) -> Option<String> {
+        let now = Instant::now();
+        let mut health = self.health.lock().expect("rpc pool lock poisoned");
+        let chain = health.entry(chain_id).or_default();
+        chain.retain(|url, _| urls.contains(url));
+        for url in urls {
+            chain.entry(url.clone()).or_default();
+        }
+
+        let candidates: Vec<&String> = urls.iter().filter(|url| !tried.contains(url)).collect();
+        let available: Vec<&String> = candidates
+            .iter()
+            .copied()
+            .filter(|url| !chain[*url].is_quarantined(now))
+            .collect();
+
+        let best = if weighted {
+            available
+                .choose_weighted(&mut rand::rng(), |url| chain[*url].weight())
+                .ok()
+                .copied()
+        } else {
+            available
+                .iter()
+                .copied()
+                .max_by(|a, b| chain[*a].weight().total_cmp(&chain[*b].weight()))
+        };
+        let picked = match best {
+            Some(url) => url.clone(),
+            None => candidates
+                .into_iter()
+                .min_by_key(|url| chain[*url].quarantined_until)?
+                .clone(),
+        };
+
+        // A quarantine that ran out makes this pick the probe, keep others
+        // off the endpoint until it reports back
+        let entry = chain.get_mut(&picked).expect("picked url is tracked");
+        if entry.quarantined_until.is_some_and(|until| until <= now) {
+            entry.quarantined_until = Some(now + PROBE_GRACE);
+        }
+
+        Some(picked)
+    }
+
+    pub fn record_success(&self, chain_id: u64, url: &str, latency: Option<Duration>) {
+        self.update(chain_id, url, |health| health.record_success(latency));
+    }
+
+    pub fn record_failure(&self, chain_id: u64, url: &str, error: &str) {
+        let now = Instant::now();
+        self.update(chain_id, url, |health| {
+            health.record_failure(error, now);
+            if health.is_quarantined(now) {
+                tracing::warn!(
+                    "RPC {} for chain {} quarantined after {} failures: {}",
+                    url,
+                    chain_id,
+                    health.consecutive_failures,
+                    error
+                );
+            }
+        });
+    }
+
+    /// Feeds the result of a mevlog run back into the score of the RPC it
+    /// was pointed at. Errors unrelated to the RPC are ignored.
+    pub fn record_run<T>(&self, cmd: &MevlogCmd, result: &Result<T, MevlogError>) {
+        let (Some(chain_id), Some(url)) = (cmd.chain_id(), cmd.rpc_url()) else {
+            return;
+        };
+        match result {
+            Ok(_) => self.record_success(chain_id, url, None),
+            Err(e) if e.is_rpc_failure() => self.record_failure(chain_id, url, &e.to_string()),
+            Err(_) => {}
+        }
+    }
+
+    pub fn stats(&self) -> HashMap<u64, Vec<RpcHealthStats>> {
+        let now = Instant::now();
+        let health = self.health.lock().expect("rpc pool lock poisoned");
+        health
+            .iter()
+            .map(|(chain_id, urls)| {
+                let stats = urls
+                    .iter()
+                    .map(|(url, health)| RpcHealthStats {
+                        url: url.clone(),
+                        success_rate: health.success_ewma,
+                        latency_ms: health.latency_ewma_ms.map(|ms| ms as u64),
+                        successes: health.successes,
+                        failures: health.failures,
+                        quarantined_for_secs: health
+                            .quarantined_until
+                            .filter(|until| *until > now)
+                            .map(|until| (until - now).as_secs()),
+                        last_error: health.last_error.clone(),
+                    })
+                    .collect();
+                (*chain_id, stats)
+            })
+            .collect()
+    }
+
+    /// Only endpoints handed out by [`Self::pick`] are tracked, so the map
+    /// stays bounded by what chain-info lists.
+    fn update(&self, chain_id: u64, url: &str, f: impl FnOnce(&mut RpcHealth)) {
+        let mut health = self.health.lock().expect("rpc pool lock poisoned");
+        if let Some(entry) = health
+            .get_mut(&chain_id)
+            .and_then(|chain| chain.get_mut(url))
+        {
+            f(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls() -> Vec<String> {
+        vec!["http://a".to_string(), "http://b".to_string()]
+    }
+
+    #[test]
+    fn quarantines_failing_endpoint() {
+        let pool = RpcPool::default();
+        pool.pick(1, &urls());
+
+        for _ in 0..QUARANTINE_AFTER {
+            pool.record_failure(1, "http://a", "No matching");
+        }
+
+        for _ in 0..20 {
+            assert_eq!(pool.pick(1, &urls()).as_deref(), Some("http://b"));
+        }
+        let stats = &pool.stats()[&1];
+        let a = stats.iter().find(|s| s.url == "http://a").unwrap();
+        assert!(a.quarantined_for_secs.is_some());
+        assert_eq!(a.last_error.as_deref(), Some("No matching"));
+    }
+
+    #[test]
+    fn reprobes_once_quarantine_ends() {
+        let pool = RpcPool::default();
+        let only_a = vec!["http://a".to_string()];
+        pool.pick(1, &only_a);
+        for _ in 0..QUARANTINE_AFTER {
+            pool.record_failure(1, "http://a", "timeout");
+        }
+
+        // Still handed out when nothing else is left
+        assert_eq!(pool.pick(1, &only_a).as_deref(), Some("http://a"));
+
+        pool.record_success(1, "http://a", None);
+        assert_eq!(pool.stats()[&1][0].quarantined_for_secs, None);
+
+        // Failing again after recovering starts over from the short backoff
+        for _ in 0..QUARANTINE_AFTER {
+            pool.record_failure(1, "http://a", "timeout");
+        }
+        let quarantined_for = pool.stats()[&1][0].quarantined_for_secs.unwrap();
+        assert!(quarantined_for <= MIN_QUARANTINE.as_secs());
+    }
+
+    #[test]
+    fn prefers_reliable_and_fast_endpoints() {
+        let mut slow = RpcHealth::default();
+        slow.record_success(Some(Duration::from_millis(2000)));
+        let mut fast = RpcHealth::default();
+        fast.record_success(Some(Duration::from_millis(50)));
+        let mut flaky = RpcHealth::default();
+        flaky.record_success(Some(Duration::from_millis(50)));
+        flaky.record_failure("timeout", Instant::now());
+        flaky.record_failure("timeout", Instant::now());
+
+        assert!(fast.weight() > slow.weight());
+        assert!(fast.weight() > flaky.weight());
+    }
+
+    #[test]
+    fn fails_over_to_best_untried_endpoint() {
+        let pool = RpcPool::default();
+        let urls = vec![
+            "http://a".to_string(),
+            "http://b".to_string(),
+            "http://c".to_string(),
+        ];
+        pool.pick(1, &urls);
+        pool.record_success(1, "http://b", Some(Duration::from_millis(900)));
+        pool.record_success(1, "http://c", Some(Duration::from_millis(30)));
+
+        let tried = vec!["http://a".to_string()];
+        assert_eq!(
+            pool.pick_next(1, &urls, &tried).as_deref(),
+            Some("http://c")
+        );
+        assert_eq!(pool.pick_next(1, &urls, &urls), None);
+    }
+
+    #[test]
+    fn forgets_endpoints_no_longer_listed() {
+        let pool = RpcPool::default();
+        pool.pick(1, &urls());
+        pool.pick(1, &["http://c".to_string()]);
+
+        pool.record_failure(1, "http://a", "timeout");
+        let stats = &pool.stats()[&1];
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].url, "http://c");
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use eyre::Result;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+
+use crate::config::app_config::app_config;
+use crate::misc::{
+    file_table::FileTable,
+    rpc_utils::{fetch_block_number, fetch_chain_info},
+    utils::unix_now,
+};
+
+/// RPCs of a chain probed per run, in chain-info order.
+const PROBED_URLS_PER_CHAIN: usize = 10;
+/// Probes older than this are ignored and chain-info is used instead.
+const MAX_PROBE_AGE: Duration = Duration::from_secs(600);
+/// Blocks an RPC may lag behind the best head seen and still be used.
+const MAX_HEAD_LAG: u64 = 3;
+
+/// Where the scheduler writes probe results for the server to read.
+pub fn probe_table_path() -> PathBuf {
+    app_config().rpc_probe.file.clone().into()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcProbe {
+    pub url: String,
+    pub latency_ms: Option<u64>,
+    pub head: Option<u64>,
+    /// Blocks behind the best head any RPC of the chain reported.
+    pub lag_blocks: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl RpcProbe {
+    fn is_usable(&self) -> bool {
+        self.error.is_none() && self.lag_blocks.is_some_and(|lag| lag <= MAX_HEAD_LAG)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainProbes {
+    /// Unix timestamp of the run, in seconds.
+    pub probed_at: u64,
+    pub probes: Vec<RpcProbe>,
+}
+
+impl ChainProbes {
+    /// Usable RPCs, fastest first. `None` when the probes are too old.
+    pub fn usable_urls(&self, now: u64) -> Option<Vec<String>> {
+        if now.saturating_sub(self.probed_at) > MAX_PROBE_AGE.as_secs() {
+            return None;
+        }
+
+        let mut usable: Vec<&RpcProbe> = self.probes.iter().filter(|p| p.is_usable()).collect();
+        usable.sort_by_key(|probe| probe.latency_ms);
+        Some(usable.into_iter().map(|probe| probe.url.clone()).collect())
+    }
+}
+
+/// Probe results of every probed chain, keyed by chain id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProbeTable {
+    pub chains: HashMap<u64, ChainProbes>,
+}
+
+/// Checks latency and head freshness of the chain's RPCs.
+pub async fn probe_chain(chain_id: u64) -> Result<ChainProbes> {
+    let chain_info = fetch_chain_info(chain_id).await?;
+    let urls: Vec<String> = chain_info
+        .rpc_urls
+        .into_iter()
+        .take(PROBED_URLS_PER_CHAIN)
+        .map(|rpc| rpc.url)
+        .collect();
+
+    let results = join_all(urls.iter().map(|url| async move {
+        let start = Instant::now();
+        let head = fetch_block_number(url).await;
+        (url.clone(), head, start.elapsed())
+    }))
+    .await;
+
+    let best_head = results
+        .iter()
+        .filter_map(|(_, head, _)| head.as_ref().ok().copied())
+        .max();
+
+    let probes = results
+        .into_iter()
+        .map(|(url, head, elapsed)| match head {
+            Ok(head) => RpcProbe {
+                url,
+                latency_ms: Some(elapsed.as_millis() as u64),
+                head: Some(head),
+                lag_blocks: best_head.map(|best| best.saturating_sub(head)),
+                error: None,
+            },
+            Err(e) => RpcProbe {
+                url,
+                latency_ms: None,
+                head: None,
+                lag_blocks: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+
+    Ok(ChainProbes {
+        probed_at: unix_now(),
+        probes,
+    })
+}
+
+/// Probes every chain and replaces the probe table. Chains that fail to
+/// probe keep their previous results until those expire.
+pub async fn update_probe_table(chains: &[u64]) -> Result<()> {
+    let path = probe_table_path();
+    let mut table = match tokio::fs::read_to_string(&path).await {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+        Err(_) => ProbeTable::default(),
+    };
+
+    for chain_id in chains {
+        match probe_chain(*chain_id).await {
+            Ok(probes) => {
+                let usable = probes.probes.iter().filter(|p| p.is_usable()).count();
+                tracing::info!(
+                    "Probed {} RPCs for chain {}, {} usable",
+                    probes.probes.len(),
+                    chain_id,
+                    usable
+                );
+                table.chains.insert(*chain_id, probes);
+            }
+            Err(e) => tracing::error!("Failed to probe RPCs for chain {}: {}", chain_id, &e),
+        }
+    }
+
+    // Written aside and renamed, so the server never reads a partial file
+    let tmp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec(&table)?).await?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    Ok(())
+}
+
+static PROBE_TABLE: FileTable<ProbeTable> = FileTable::new(probe_table_path);
+
+/// Usable RPCs of the chain from the scheduler's latest probes, fastest
+/// first. `None` when the chain isn't probed or the probes are stale.
+pub async fn probed_rpc_urls(chain_id: u64) -> Option<Vec<String>> {
+    let urls = PROBE_TABLE
+        .get()
+        .await?
+        .chains
+        .get(&chain_id)?
+        .usable_urls(unix_now())?;
+    (!urls.is_empty()).then_some(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(url: &str, latency_ms: u64, lag_blocks: Option<u64>) -> RpcProbe {
+        RpcProbe {
+            url: url.to_string(),
+            latency_ms: Some(latency_ms),
+            head: lag_blocks.map(|lag| 100 - lag),
+            lag_blocks,
+            error: lag_blocks.is_none().then(|| "timeout".to_string()),
+        }
+    }
+
+    #[test]
+    fn orders_usable_urls_by_latency() {
+        let probes = ChainProbes {
+            probed_at: 1000,
+            probes: vec![
+                probe("http://slow", 400, Some(0)),
+                probe("http://behind", 10, Some(20)),
+                probe("http://down", 10, None),
+                probe("http://fast", 50, Some(1)),
+            ],
+        };
+
+        assert_eq!(
+            probes.usable_urls(1010),
+            Some(vec!["http://fast".to_string(), "http://slow".to_string()])
+        );
+        assert_eq!(probes.usable_urls(1000 + MAX_PROBE_AGE.as_secs() + 1), None);
+    }
+}
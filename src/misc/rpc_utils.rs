@@ -1,13 +1,29 @@
-use eyre::{Result, bail};
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::transports::http::reqwest;
+use eyre::{Result, bail, eyre};
 use mevlog::ChainInfoJson;
-use rand::prelude::*;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::process::Command as AsyncCommand;
 use tokio::sync::RwLock;
 
-use crate::controllers::json::base_controller::call_json_command;
+use crate::config::app_config::app_config;
+use crate::misc::{
+    custom_rpc::verify_custom_rpc,
+    metrics::METRICS,
+    mevlog_cmd::{MevlogCmd, MevlogError, run_json},
+    rpc_guard::guarded_client,
+    rpc_pool::RPC_POOL,
+    rpc_prober::probed_rpc_urls,
+};
+
+/// Which RPC finally returned the data, and after how many attempts.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServedBy {
+    pub rpc_url: String,
+    pub attempts: u32,
+}
 
 #[derive(Clone)]
 struct CachedRpcUrls {
@@ -18,13 +34,74 @@ struct CachedRpcUrls {
 type RpcCache = Arc<RwLock<HashMap<u64, CachedRpcUrls>>>;
 static RPC_URL_MEMORY_CACHE: std::sync::LazyLock<RpcCache> =
     std::sync::LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
-const CACHE_DURATION: Duration = Duration::from_secs(60); // 1 minute
+/// Attempts made for one explore or search, each against another RPC.
+pub const MAX_RPC_ATTEMPTS: u32 = 3;
+/// No new attempt is started once a request has been failing over this long.
+pub const FAILOVER_DEADLINE: Duration = Duration::from_secs(20);
+/// Fastest endpoints from chain-info kept in rotation.
+const RPC_URLS_PER_CHAIN: usize = 5;
+const BLOCK_NUMBER_TIMEOUT: Duration = Duration::from_secs(2);
 
+/// One of the chain's public RPCs, weighted by how it has been doing, see
+/// [`RPC_POOL`].
 #[hotpath::measure(log = true)]
-pub async fn get_random_rpc_url(chain_id: u64) -> Result<Option<String>> {
+pub async fn get_rpc_url(chain_id: u64) -> Result<Option<String>> {
+    let urls = get_cached_rpc_urls(chain_id).await?;
+    Ok(RPC_POOL.pick(chain_id, &urls))
+}
+
+/// The RPC a request runs against: the user's own one once
+/// [`verify_custom_rpc`] accepted it, otherwise one of the chain's public
+/// RPCs.
+pub async fn resolve_rpc_url(
+    chain_id: u64,
+    custom: Option<&str>,
+) -> Result<Option<String>, MevlogError> {
+    match custom {
+        Some(custom) => verify_custom_rpc(chain_id, custom)
+            .await
+            .map(Some)
+            .map_err(MevlogError::RpcRejected),
+        None => Ok(get_rpc_url(chain_id).await.ok().flatten()),
+    }
+}
+
+/// The RPC for the next attempt of a request: a weighted pick for the
+/// first one, then the best scored RPC not in `tried`. `None` once all of
+/// them were tried.
+pub async fn get_next_rpc_url(chain_id: u64, tried: &[String]) -> Result<Option<String>> {
+    if tried.is_empty() {
+        return get_rpc_url(chain_id).await;
+    }
     let urls = get_cached_rpc_urls(chain_id).await?;
-    let mut rng = rand::rng();
-    Ok(urls.choose(&mut rng).cloned())
+    Ok(RPC_POOL.pick_next(chain_id, &urls, tried))
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcUrlCacheStats {
+    pub chain_id: u64,
+    pub urls: Vec<String>,
+    pub age: Duration,
+    /// Fetched again on the next lookup.
+    pub expired: bool,
+}
+
+/// Each chain's cached RPC list, for `/metrics` and `/admin/status`.
+pub async fn rpc_url_cache_stats() -> Vec<RpcUrlCacheStats> {
+    let max_age = Duration::from_secs(app_config().rpc.url_cache_secs);
+    let mut stats: Vec<RpcUrlCacheStats> = RPC_URL_MEMORY_CACHE
+        .read()
+        .await
+        .iter()
+        .map(|(chain_id, cached)| RpcUrlCacheStats {
+            chain_id: *chain_id,
+            urls: cached.urls.clone(),
+            age: cached.cached_at.elapsed(),
+            expired: cached.cached_at.elapsed() >= max_age,
+        })
+        .collect();
+    stats.sort_by_key(|stats| stats.chain_id);
+    stats
 }
 
 #[hotpath::measure(log = true)]
@@ -32,19 +109,25 @@ async fn get_cached_rpc_urls(chain_id: u64) -> Result<Vec<String>> {
     {
         let cache_read = RPC_URL_MEMORY_CACHE.read().await;
         if let Some(cached) = cache_read.get(&chain_id)
-            && cached.cached_at.elapsed() < CACHE_DURATION
+            && cached.cached_at.elapsed() < Duration::from_secs(app_config().rpc.url_cache_secs)
         {
+            METRICS.record_cache_lookup("rpc_urls", true);
             return Ok(cached.urls.clone());
         }
     }
+    METRICS.record_cache_lookup("rpc_urls", false);
 
-    let chain_info = fetch_chain_info(chain_id).await?;
-    let top_rpc_urls: Vec<String> = chain_info
-        .rpc_urls
-        .into_iter()
-        .take(3)
-        .map(|rpc| rpc.url)
-        .collect();
+    // Prefer what the scheduler measured over chain-info's snapshot
+    let top_rpc_urls: Vec<String> = match probed_rpc_urls(chain_id).await {
+        Some(urls) => urls.into_iter().take(RPC_URLS_PER_CHAIN).collect(),
+        None => fetch_chain_info(chain_id)
+            .await?
+            .rpc_urls
+            .into_iter()
+            .take(RPC_URLS_PER_CHAIN)
+            .map(|rpc| rpc.url)
+            .collect(),
+    };
 
     {
         let mut cache_write = RPC_URL_MEMORY_CACHE.write().await;
@@ -61,16 +144,85 @@ async fn get_cached_rpc_urls(chain_id: u64) -> Result<Vec<String>> {
 }
 
 #[hotpath::measure(log = true)]
-async fn fetch_chain_info(chain_id: u64) -> Result<ChainInfoJson> {
-    let mut cmd = AsyncCommand::new("mevlog");
-    cmd.arg("chain-info")
+pub async fn fetch_chain_info(chain_id: u64) -> Result<ChainInfoJson> {
+    let mut cmd = MevlogCmd::new("chain-info");
+    cmd.chain(chain_id)
         .arg("--chain-id")
         .arg(chain_id.to_string())
         .arg("--format")
         .arg("json");
 
-    match call_json_command::<ChainInfoJson>(&mut cmd).await {
+    match run_json::<ChainInfoJson>(&cmd).await {
         Ok(chain_info) => Ok(chain_info),
         Err(e) => bail!("Failed to get chain info for chain_id {chain_id}: {e}",),
     }
 }
+
+/// Current head of the chain served by `rpc_url`, asked through
+/// [`guarded_client`].
+pub async fn fetch_block_number(rpc_url: &str) -> Result<u64> {
+    fetch_block_number_with(guarded_client()?, rpc_url).await
+}
+
+/// Same as [`fetch_block_number`] for an RPC the operator configured, which
+/// may be on a private network.
+pub async fn fetch_trusted_block_number(rpc_url: &str) -> Result<u64> {
+    fetch_block_number_with(Default::default(), rpc_url).await
+}
+
+#[hotpath::measure(log = true)]
+async fn fetch_block_number_with(client: reqwest::Client, rpc_url: &str) -> Result<u64> {
+    let provider = ProviderBuilder::new().connect_reqwest(client, rpc_url.parse()?);
+    let block_number =
+        tokio::time::timeout(BLOCK_NUMBER_TIMEOUT, provider.get_block_number()).await??;
+    Ok(block_number)
+}
+
+/// Current head of `chain_id`, asked from one of its public RPCs.
+pub async fn fetch_chain_head(chain_id: u64) -> Result<u64> {
+    let rpc_url = get_rpc_url(chain_id)
+        .await?
+        .ok_or_else(|| eyre!("No RPC URL for chain {chain_id}"))?;
+
+    let start = Instant::now();
+    match fetch_block_number(&rpc_url).await {
+        Ok(block_number) => {
+            RPC_POOL.record_success(chain_id, &rpc_url, Some(start.elapsed()));
+            Ok(block_number)
+        }
+        Err(e) => {
+            RPC_POOL.record_failure(chain_id, &rpc_url, &e.to_string());
+            Err(e)
+        }
+    }
+}
+
+/// Unix timestamp of a block of `chain_id`, asked from one of its public
+/// RPCs.
+pub async fn fetch_block_timestamp(chain_id: u64, block_number: u64) -> Result<u64> {
+    let rpc_url = get_rpc_url(chain_id)
+        .await?
+        .ok_or_else(|| eyre!("No RPC URL for chain {chain_id}"))?;
+
+    let provider = ProviderBuilder::new().connect_http(rpc_url.parse()?);
+    let start = Instant::now();
+    let block = tokio::time::timeout(
+        BLOCK_NUMBER_TIMEOUT,
+        provider.get_block_by_number(block_number.into()),
+    )
+    .await
+    .map_err(|_| eyre!("Timed out fetching block {block_number}"))
+    .and_then(|block| Ok(block?));
+
+    match block {
+        Ok(Some(block)) => {
+            RPC_POOL.record_success(chain_id, &rpc_url, Some(start.elapsed()));
+            Ok(block.header.timestamp)
+        }
+        Ok(None) => bail!("Block {block_number} of chain {chain_id} not found"),
+        Err(e) => {
+            RPC_POOL.record_failure(chain_id, &rpc_url, &e.to_string());
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::config::app_config::app_config;
+use crate::misc::utils::unix_now;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    /// Unix timestamps, in seconds.
+    pub last_run: u64,
+    pub last_success: Option<u64>,
+    /// Error of the last run, `None` when it succeeded.
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WarmerStatus {
+    /// Last block the warmer ran mevlog for.
+    pub head_block: Option<u64>,
+    /// Unix timestamp of the last update, in seconds.
+    pub updated_at: u64,
+    pub last_error: Option<String>,
+}
+
+/// What the scheduler has been up to, written by the scheduler process for
+/// the server's `/metrics` and `/admin/status`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerStatus {
+    pub jobs: BTreeMap<String, JobStatus>,
+    /// Keyed by chain id.
+    pub warmers: BTreeMap<u64, WarmerStatus>,
+}
+
+pub fn scheduler_status_path() -> PathBuf {
+    app_config().scheduler.status_file.clone().into()
+}
+
+static STATUS: LazyLock<Mutex<SchedulerStatus>> =
+    LazyLock::new(|| Mutex::new(SchedulerStatus::default()));
+
+/// Records the outcome of a scheduler job run.
+pub async fn record_job_run(job: &str, result: Result<(), String>) {
+    update(|status| {
+        let now = unix_now();
+        let job = status.jobs.entry(job.to_string()).or_default();
+        job.last_run = now;
+        match result {
+            Ok(_) => {
+                job.last_success = Some(now);
+                job.last_error = None;
+            }
+            Err(e) => job.last_error = Some(e),
+        }
+    })
+    .await;
+}
+
+/// Records the block a cache warmer just warmed.
+pub async fn record_warmer_head(chain_id: u64, head_block: u64) {
+    update(|status| {
+        let warmer = status.warmers.entry(chain_id).or_default();
+        warmer.head_block = Some(head_block);
+        warmer.updated_at = unix_now();
+        warmer.last_error = None;
+    })
+    .await;
+}
+
+pub async fn record_warmer_error(chain_id: u64, error: String) {
+    update(|status| {
+        let warmer = status.warmers.entry(chain_id).or_default();
+        warmer.updated_at = unix_now();
+        warmer.last_error = Some(error);
+    })
+    .await;
+}
+
+async fn update(change: impl FnOnce(&mut SchedulerStatus)) {
+    let mut status = STATUS.lock().await;
+    change(&mut status);
+    if let Err(e) = write_status(&status).await {
+        tracing::error!("Failed to write scheduler status: {}", &e);
+    }
+}
+
+async fn write_status(status: &SchedulerStatus) -> Result<()> {
+    // Written aside and renamed, so the server never reads a partial file
+    let path = scheduler_status_path();
+    let tmp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec(status)?).await?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    Ok(())
+}
+
+/// The scheduler's latest status, empty until it wrote one.
+pub async fn scheduler_status() -> SchedulerStatus {
+    match tokio::fs::read_to_string(scheduler_status_path()).await {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+        Err(_) => SchedulerStatus::default(),
+    }
+}
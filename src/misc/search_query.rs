@@ -0,0 +1,394 @@
+use crate::config::app_config::app_config;
+use crate::controllers::{
+    base_controller::get_default_blocks, html::search_controller::SearchParams,
+};
+use crate::misc::mevlog_cmd::MevlogCmd;
+use crate::misc::validation::{canonical_comparison, canonical_erc20_transfer, max_block_range};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchFormat {
+    /// All matches printed as a single JSON array.
+    Json,
+    /// One JSON transaction per line as soon as it's found.
+    JsonStream,
+}
+
+impl SearchFormat {
+    fn as_arg(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::JsonStream => "json-stream",
+        }
+    }
+}
+
+/// Transaction filters supported by `mevlog search`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchFilters {
+    pub position: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub event: Option<String>,
+    pub not_event: Option<String>,
+    pub method: Option<String>,
+    pub erc20_transfer: Option<String>,
+    pub tx_cost: Option<String>,
+    pub gas_price: Option<String>,
+}
+
+impl SearchFilters {
+    fn flags(&self) -> [(&'static str, &Option<String>); 9] {
+        [
+            ("-p", &self.position),
+            ("--from", &self.from),
+            ("--to", &self.to),
+            ("--event", &self.event),
+            ("--not-event", &self.not_event),
+            ("--method", &self.method),
+            ("--erc20-transfer", &self.erc20_transfer),
+            ("--tx-cost", &self.tx_cost),
+            ("--gas-price", &self.gas_price),
+        ]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags().iter().all(|(_, value)| value.is_none())
+    }
+}
+
+impl From<&SearchParams> for SearchFilters {
+    fn from(params: &SearchParams) -> Self {
+        Self {
+            position: params.position.clone(),
+            from: params.from.clone(),
+            to: params.to.clone(),
+            event: params.event.clone(),
+            not_event: params.not_event.clone(),
+            method: params.method.clone(),
+            erc20_transfer: params
+                .erc20_transfer
+                .as_deref()
+                .map(canonical_erc20_transfer),
+            tx_cost: params.tx_cost.as_deref().map(canonical_comparison),
+            gas_price: params.gas_price.as_deref().map(canonical_comparison),
+        }
+    }
+}
+
+/// A concrete, inclusive block range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRange {
+    pub from: u64,
+    pub to: u64,
+}
+
+impl BlockRange {
+    /// Resolves a `blocks` value to block numbers. `latest` and `N:latest`
+    /// (the last N blocks) need the `latest` block number.
+    pub fn resolve(blocks: &str, latest: Option<u64>) -> Option<Self> {
+        match blocks.trim().split_once(':') {
+            None if blocks.trim() == "latest" => latest.map(|latest| Self {
+                from: latest,
+                to: latest,
+            }),
+            None => {
+                let block = blocks.trim().parse().ok()?;
+                Some(Self {
+                    from: block,
+                    to: block,
+                })
+            }
+            Some((count, "latest")) => {
+                let count = count.parse::<u64>().ok()?.max(1);
+                let latest = latest?;
+                Some(Self {
+                    from: latest.saturating_sub(count - 1),
+                    to: latest,
+                })
+            }
+            Some((from, to)) => Some(Self {
+                from: from.parse().ok()?,
+                to: to.parse().ok()?,
+            }),
+        }
+    }
+
+    pub fn is_relative(blocks: &str) -> bool {
+        blocks.contains("latest")
+    }
+}
+
+impl std::fmt::Display for BlockRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.from, self.to)
+    }
+}
+
+/// A `mevlog search` run. Every caller builds one of these and converts it
+/// with [`SearchQuery::to_cmd`], so filters are serialized the same way
+/// for explore, WebSocket search and the scheduler's cache warmer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    pub chain_id: u64,
+    pub blocks: String,
+    pub filters: SearchFilters,
+    pub format: Option<SearchFormat>,
+    pub rpc_url: Option<String>,
+    /// `rpc_url` is the operator's own, see [`MevlogCmd::trust_rpc`].
+    pub trusted_rpc: bool,
+    pub native_token_price: Option<f64>,
+    pub rpc_timeout_ms: Option<u64>,
+    pub latest_offset: Option<u64>,
+    pub batch_size: Option<u64>,
+    pub max_range: Option<u64>,
+}
+
+impl SearchQuery {
+    pub fn new(chain_id: u64, blocks: impl Into<String>) -> Self {
+        Self {
+            chain_id,
+            blocks: blocks.into(),
+            filters: SearchFilters::default(),
+            format: None,
+            rpc_url: None,
+            trusted_rpc: false,
+            native_token_price: None,
+            rpc_timeout_ms: None,
+            latest_offset: None,
+            batch_size: None,
+            max_range: None,
+        }
+    }
+
+    pub fn from_params(params: &SearchParams) -> Self {
+        Self {
+            filters: SearchFilters::from(params),
+            ..Self::new(
+                params.chain_id.unwrap_or(1),
+                get_default_blocks(params.blocks.clone()),
+            )
+        }
+    }
+
+    /// Settings shared by the endpoints that stream matches as they're found.
+    pub fn streaming(params: &SearchParams) -> Self {
+        Self {
+            format: Some(SearchFormat::JsonStream),
+            latest_offset: Some(1), // Improves caching
+            batch_size: Some(app_config().search.batch_size),
+            max_range: Some(max_block_range()),
+            ..Self::from_params(params)
+        }
+    }
+
+    pub fn to_cmd(&self) -> MevlogCmd {
+        let mut cmd = MevlogCmd::new("search");
+        cmd.chain(self.chain_id).arg("-b").arg(&self.blocks);
+
+        if let Some(format) = self.format {
+            cmd.arg("--format").arg(format.as_arg());
+        }
+
+        for (flag, value) in self.filters.flags() {
+            if let Some(value) = value {
+                cmd.arg(flag).arg(value);
+            }
+        }
+
+        let tuning = [
+            ("--rpc-timeout-ms", self.rpc_timeout_ms),
+            ("--latest-offset", self.latest_offset),
+            ("--batch-size", self.batch_size),
+            ("--max-range", self.max_range),
+        ];
+        for (flag, value) in tuning {
+            if let Some(value) = value {
+                cmd.arg(flag).arg(value.to_string());
+            }
+        }
+
+        if let Some(price) = self.native_token_price {
+            cmd.arg("--native-token-price").arg(price.to_string());
+        }
+
+        cmd.arg("--chain-id").arg(self.chain_id.to_string());
+        // The chain is already known, don't spend an RPC call confirming it
+        if let Some(rpc_url) = &self.rpc_url {
+            cmd.arg("--rpc-url")
+                .arg(rpc_url)
+                .arg("--skip-verify-chain-id");
+        }
+        if self.trusted_rpc {
+            cmd.trust_rpc();
+        }
+
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_filters() -> SearchFilters {
+        SearchFilters {
+            position: Some("0:5".to_string()),
+            from: Some("jaredfromsubway.eth".to_string()),
+            to: Some("0x51c72848c68a965f66fa7a88855f9f7784502a7f".to_string()),
+            event: Some("/(Swap).+/".to_string()),
+            not_event: Some("Transfer(address,address,uint256)".to_string()),
+            method: Some("transfer".to_string()),
+            erc20_transfer: Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string()),
+            tx_cost: Some("ge0.01ether".to_string()),
+            gas_price: Some("le2gwei".to_string()),
+        }
+    }
+
+    fn filter_args(cmd: &MevlogCmd) -> Vec<(String, String)> {
+        cmd.args()
+            .windows(2)
+            .filter(|pair| {
+                [
+                    "-p",
+                    "--from",
+                    "--to",
+                    "--event",
+                    "--not-event",
+                    "--method",
+                    "--erc20-transfer",
+                    "--tx-cost",
+                    "--gas-price",
+                ]
+                .contains(&pair[0].as_str())
+            })
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect()
+    }
+
+    #[test]
+    fn serializes_every_filter() {
+        let query = SearchQuery {
+            filters: all_filters(),
+            ..SearchQuery::new(1, "latest")
+        };
+
+        assert_eq!(
+            filter_args(&query.to_cmd()),
+            vec![
+                ("-p".to_string(), "0:5".to_string()),
+                ("--from".to_string(), "jaredfromsubway.eth".to_string()),
+                (
+                    "--to".to_string(),
+                    "0x51c72848c68a965f66fa7a88855f9f7784502a7f".to_string()
+                ),
+                ("--event".to_string(), "/(Swap).+/".to_string()),
+                (
+                    "--not-event".to_string(),
+                    "Transfer(address,address,uint256)".to_string()
+                ),
+                ("--method".to_string(), "transfer".to_string()),
+                (
+                    "--erc20-transfer".to_string(),
+                    "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string()
+                ),
+                ("--tx-cost".to_string(), "ge0.01ether".to_string()),
+                ("--gas-price".to_string(), "le2gwei".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_do_not_depend_on_tuning() {
+        let base = SearchQuery {
+            filters: all_filters(),
+            ..SearchQuery::new(137, "100:200")
+        };
+        let tuned = SearchQuery {
+            format: Some(SearchFormat::JsonStream),
+            rpc_url: Some("https://polygon.example".to_string()),
+            native_token_price: Some(0.25),
+            rpc_timeout_ms: Some(500),
+            latest_offset: Some(1),
+            batch_size: Some(20),
+            max_range: Some(500),
+            ..base.clone()
+        };
+
+        assert_eq!(filter_args(&base.to_cmd()), filter_args(&tuned.to_cmd()));
+    }
+
+    #[test]
+    fn skips_empty_filters() {
+        let cmd = SearchQuery::new(1, "latest").to_cmd();
+
+        assert_eq!(cmd.args(), ["search", "-b", "latest", "--chain-id", "1"]);
+        assert!(SearchFilters::default().is_empty());
+    }
+
+    #[test]
+    fn rpc_url_skips_chain_verification() {
+        let query = SearchQuery {
+            format: Some(SearchFormat::Json),
+            rpc_url: Some("https://eth.example".to_string()),
+            native_token_price: Some(2500.5),
+            ..SearchQuery::new(1, "22045570")
+        };
+        let cmd = query.to_cmd();
+
+        assert_eq!(cmd.chain_id(), Some(1));
+        assert_eq!(cmd.flag_value("--format"), Some("json"));
+        assert_eq!(cmd.flag_value("--native-token-price"), Some("2500.5"));
+        assert_eq!(cmd.flag_value("--chain-id"), Some("1"));
+        assert_eq!(cmd.flag_value("--rpc-url"), Some("https://eth.example"));
+        assert!(cmd.has_flag("--skip-verify-chain-id"));
+    }
+
+    #[test]
+    fn resolves_block_ranges() {
+        let range = |from, to| Some(BlockRange { from, to });
+
+        assert_eq!(
+            BlockRange::resolve("22045570", None),
+            range(22045570, 22045570)
+        );
+        assert_eq!(BlockRange::resolve("100:200", None), range(100, 200));
+        assert_eq!(BlockRange::resolve("latest", Some(500)), range(500, 500));
+        assert_eq!(BlockRange::resolve("10:latest", Some(500)), range(491, 500));
+        assert_eq!(BlockRange::resolve("10:latest", None), None);
+        assert_eq!(BlockRange::resolve("latest", None), None);
+        assert_eq!(range(491, 500).unwrap().to_string(), "491:500");
+    }
+
+    #[test]
+    fn builds_from_search_params() {
+        let params = SearchParams {
+            blocks: None,
+            position: None,
+            from: Some("0xbaa3ef11659d347aae75c7bb67e29f1f8bb90843".to_string()),
+            to: None,
+            event: None,
+            not_event: None,
+            method: Some("swap".to_string()),
+            erc20_transfer: None,
+            tx_cost: Some(">=0.5 eth".to_string()),
+            gas_price: None,
+            chain_id: Some(8453),
+            rpc_url: None,
+        };
+        let query = SearchQuery::from_params(&params);
+
+        assert_eq!(query.chain_id, 8453);
+        assert_eq!(query.blocks, "latest");
+        assert_eq!(
+            filter_args(&query.to_cmd()),
+            vec![
+                (
+                    "--from".to_string(),
+                    "0xbaa3ef11659d347aae75c7bb67e29f1f8bb90843".to_string()
+                ),
+                ("--method".to_string(), "swap".to_string()),
+                ("--tx-cost".to_string(), "ge0.5ether".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::app_config::app_config;
+use crate::controllers::base_controller::{DATA_FETCH_ERROR, decorate_error_message};
+use crate::misc::mevlog_cmd::{MevlogError, MevlogStream, RunEnd, run_stream};
+use crate::misc::price_history::{PriceQuote, price_for_blocks};
+use crate::misc::request_id::with_request_id;
+use crate::misc::rpc_utils::{
+    FAILOVER_DEADLINE, MAX_RPC_ATTEMPTS, ServedBy, get_next_rpc_url, resolve_rpc_url,
+};
+use crate::misc::search_query::SearchQuery;
+use crate::misc::shutdown::shutdown_started;
+
+/// Attempts aren't started with less time than this left before
+/// [`FAILOVER_DEADLINE`].
+const MIN_ATTEMPT_TIME: Duration = Duration::from_secs(2);
+
+/// How long a streaming search may run, `[search] timeout_secs`.
+pub fn search_timeout() -> Duration {
+    Duration::from_secs(app_config().search.timeout_secs)
+}
+
+/// What a streaming `mevlog search` produces, independent of the transport
+/// it's delivered over.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchEvent {
+    /// A matching transaction.
+    Match(Value),
+    /// A user-facing error reported by mevlog.
+    Error(String),
+    /// The search ran past [`search_timeout`].
+    TimedOut,
+}
+
+impl SearchEvent {
+    /// Whether the event means the RPC couldn't serve the data, so another
+    /// one might.
+    pub fn is_rpc_failure(&self) -> bool {
+        match self {
+            Self::TimedOut => true,
+            Self::Error(e) => e == DATA_FETCH_ERROR,
+            Self::Match(_) => false,
+        }
+    }
+
+    /// The JSON payload the WebSocket handler sends for the same event.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Self::Match(tx) => tx.clone(),
+            Self::Error(e) => with_request_id(serde_json::json!({ "error": e })),
+            Self::TimedOut => with_request_id(serde_json::json!({ "error": DATA_FETCH_ERROR })),
+        }
+    }
+}
+
+/// Converts mevlog output into events and pushes them to `tx` until the
+/// process finishes, the receiver is dropped, the time limit passes or
+/// the server shuts down. Events mapped to `None` are skipped.
+pub async fn forward_search_events<T>(
+    stream: &mut MevlogStream,
+    tx: &mpsc::Sender<T>,
+    to_item: impl FnMut(SearchEvent) -> Option<T>,
+) -> RunEnd {
+    forward_search_events_within(stream, tx, search_timeout(), to_item).await
+}
+
+/// [`forward_search_events`] with a time limit other than
+/// [`search_timeout`].
+pub async fn forward_search_events_within<T>(
+    stream: &mut MevlogStream,
+    tx: &mpsc::Sender<T>,
+    timeout: Duration,
+    mut to_item: impl FnMut(SearchEvent) -> Option<T>,
+) -> RunEnd {
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    let mut stdout_done = false;
+    let mut stderr_done = false;
+
+    loop {
+        if stdout_done && stderr_done {
+            return RunEnd::Completed;
+        }
+
+        let event = tokio::select! {
+            _ = &mut deadline => {
+                if let Some(item) = to_item(SearchEvent::TimedOut)
+                    && tx.send(item).await.is_err()
+                {
+                    return RunEnd::ClientDisconnected;
+                }
+                return RunEnd::TimedOut;
+            }
+            _ = tx.closed() => {
+                return RunEnd::ClientDisconnected;
+            }
+            _ = shutdown_started() => {
+                return RunEnd::ShuttingDown;
+            }
+            line = stream.stdout.next(), if !stdout_done => {
+                match line {
+                    Some(Ok(line)) => match serde_json::from_str::<Value>(&line) {
+                        Ok(tx_json) => SearchEvent::Match(tx_json),
+                        Err(_) => {
+                            tracing::warn!("Skipping non-JSON mevlog output: {}", line);
+                            continue;
+                        }
+                    },
+                    Some(Err(_)) => continue,
+                    None => {
+                        stdout_done = true;
+                        continue;
+                    }
+                }
+            }
+            line = stream.stderr.next(), if !stderr_done => {
+                match line {
+                    Some(Ok(line)) => {
+                        let error = decorate_error_message(&line);
+                        if error == DATA_FETCH_ERROR {
+                            stream.record_rpc_failure(line);
+                        }
+                        SearchEvent::Error(error)
+                    }
+                    Some(Err(_)) => continue,
+                    None => {
+                        stderr_done = true;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if let Some(item) = to_item(event)
+            && tx.send(item).await.is_err()
+        {
+            return RunEnd::ClientDisconnected;
+        }
+    }
+}
+
+/// What [`run_search`] reports to the handler delivering the search.
+pub enum SearchUpdate {
+    /// An attempt started, a search fails over to another RPC at most
+    /// [`MAX_RPC_ATTEMPTS`] times.
+    Started {
+        served_by: Option<ServedBy>,
+        price: Option<PriceQuote>,
+    },
+    Event(SearchEvent),
+    Failed(MevlogError),
+    Finished(RunEnd),
+}
+
+/// How far a search got, reported in progress and done messages.
+#[derive(Debug, Clone)]
+pub struct SearchProgress {
+    started_at: Instant,
+    pub matches: u64,
+    pub last_block: Option<u64>,
+    pub served_by: Option<ServedBy>,
+    pub price: Option<PriceQuote>,
+}
+
+impl Default for SearchProgress {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            matches: 0,
+            last_block: None,
+            served_by: None,
+            price: None,
+        }
+    }
+}
+
+impl SearchProgress {
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started_at.elapsed().as_millis() as u64
+    }
+
+    /// Counts a match. Returns whether it's the first one of a block, which
+    /// is when progress is reported.
+    pub fn record_match(&mut self, tx: &Value) -> bool {
+        let block = tx["block_number"].as_u64();
+        self.matches += 1;
+        if block.is_some() && block != self.last_block {
+            self.last_block = block;
+            return true;
+        }
+        false
+    }
+}
+
+/// Runs the search, failing over to the next best RPC when one can't
+/// serve the data and nothing matched yet. Errors of an attempt that's
+/// retried are not reported. A user-supplied `rpc_url` is used as is.
+pub async fn run_search(
+    query: SearchQuery,
+    rpc_url: Option<String>,
+    updates: mpsc::Sender<SearchUpdate>,
+    mut cancel: oneshot::Receiver<()>,
+) {
+    let mut query = query;
+    let price = price_for_blocks(query.chain_id, &query.blocks).await;
+    query.native_token_price = price.map(|price| price.usd);
+    let deadline = Instant::now() + FAILOVER_DEADLINE;
+    let mut tried: Vec<String> = vec![];
+
+    let custom_rpc_url = match &rpc_url {
+        Some(rpc_url) => match resolve_rpc_url(query.chain_id, Some(rpc_url)).await {
+            Ok(rpc_url) => rpc_url,
+            Err(e) => {
+                let _ = updates.send(SearchUpdate::Failed(e)).await;
+                return;
+            }
+        },
+        None => None,
+    };
+
+    loop {
+        let mut query = query.clone();
+        query.rpc_url = match &custom_rpc_url {
+            Some(rpc_url) => Some(rpc_url.clone()),
+            None => get_next_rpc_url(query.chain_id, &tried)
+                .await
+                .ok()
+                .flatten(),
+        };
+        let served_by = query.rpc_url.clone().map(|rpc_url| {
+            tried.push(rpc_url.clone());
+            ServedBy {
+                rpc_url,
+                attempts: tried.len() as u32,
+            }
+        });
+
+        let cmd = query.to_cmd();
+
+        let stream = tokio::select! {
+            biased;
+            _ = &mut cancel => return,
+            stream = run_stream(&cmd) => stream,
+        };
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::error!("Failed to start mevlog search: {}", &e);
+                let _ = updates.send(SearchUpdate::Failed(e)).await;
+                return;
+            }
+        };
+
+        let _ = updates
+            .send(SearchUpdate::Started {
+                served_by: served_by.clone(),
+                price,
+            })
+            .await;
+
+        let mut matched = false;
+        let mut rpc_failure: Option<SearchEvent> = None;
+        let timeout = search_timeout().min(deadline.saturating_duration_since(Instant::now()));
+        let end = tokio::select! {
+            biased;
+            cancelled = &mut cancel => match cancelled {
+                Ok(()) => RunEnd::Cancelled,
+                Err(_) => RunEnd::ClientDisconnected,
+            },
+            end = forward_search_events_within(&mut stream, &updates, timeout, |event| {
+                match event {
+                    SearchEvent::Match(_) => matched = true,
+                    // Held back until it's clear whether another RPC is tried
+                    _ if !matched && event.is_rpc_failure() => {
+                        rpc_failure = Some(event);
+                        return None;
+                    }
+                    _ => {}
+                }
+                Some(SearchUpdate::Event(event))
+            }) => end,
+        };
+
+        stream.finish(end).await;
+
+        if let Some(event) = rpc_failure {
+            let retry = served_by.is_some()
+                && custom_rpc_url.is_none()
+                && tried.len() < MAX_RPC_ATTEMPTS as usize
+                && deadline.saturating_duration_since(Instant::now()) >= MIN_ATTEMPT_TIME
+                && matches!(end, RunEnd::Completed | RunEnd::TimedOut)
+                && get_next_rpc_url(query.chain_id, &tried)
+                    .await
+                    .is_ok_and(|next| next.is_some());
+            if retry {
+                tracing::warn!(
+                    "Search on chain {} failed with {:?}, retrying",
+                    query.chain_id,
+                    served_by.map(|served_by| served_by.rpc_url)
+                );
+                continue;
+            }
+            let _ = updates.send(SearchUpdate::Event(event)).await;
+        }
+
+        let _ = updates.send(SearchUpdate::Finished(end)).await;
+        return;
+    }
+}
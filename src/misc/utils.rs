@@ -1,9 +1,5 @@
 use eyre::Result;
-use std::time::{Duration, Instant};
-
-pub fn deployed_at() -> String {
-    std::env::var("DEPLOYED_AT").unwrap()
-}
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub fn measure_start(label: &str) -> (String, Instant) {
     (label.to_string(), Instant::now())
@@ -15,6 +11,20 @@ pub fn measure_end(start: (String, Instant)) -> Duration {
     elapsed
 }
 
+/// Seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Compares without returning early, so a secret can't be guessed from
+/// response times.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 pub fn format_duration(duration: Duration) -> String {
     let secs = duration.as_secs();
     let millis = duration.subsec_millis();
@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::app_config::app_config;
+use crate::misc::request_id::with_request_id;
+
+/// Largest block range a single search may scan, matches `--max-range`.
+pub fn max_block_range() -> u64 {
+    app_config().search.max_block_range
+}
+
+const AMOUNT_UNITS: [&str; 3] = ["ether", "gwei", "wei"];
+
+static ADDRESS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^0x[0-9a-fA-F]{40}$").unwrap());
+static ENS_NAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i)([a-z0-9_-]+\.)+eth$").unwrap());
+static TOPIC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^0x[0-9a-fA-F]{64}$").unwrap());
+static SELECTOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^0x[0-9a-fA-F]{8}$").unwrap());
+static IDENTIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z_$][A-Za-z0-9_$]*$").unwrap());
+static SIGNATURE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z_$][A-Za-z0-9_$]*\(([A-Za-z0-9_\[\](),]*)\)$").unwrap());
+static COMPARISON: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(ge|le|>=|<=|>|<)\s*([0-9]+(?:\.[0-9]+)?)\s*([A-Za-z]*)$").unwrap()
+});
+
+/// Validation messages keyed by the query parameter they refer to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidationErrors(BTreeMap<&'static str, String>);
+
+impl ValidationErrors {
+    pub fn check(&mut self, field: &'static str, result: Result<(), String>) {
+        if let Err(e) = result {
+            self.0.insert(field, e);
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0.get(field).map(String::as_str)
+    }
+
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.0.is_empty() { Ok(()) } else { Err(self) }
+    }
+
+    pub fn to_json(&self) -> Value {
+        with_request_id(serde_json::json!({
+            "error": self.to_string(),
+            "errors": self.0,
+        }))
+    }
+
+    /// One `field: message` line per invalid parameter. The messages quote
+    /// user input, so they must be HTML-escaped wherever they're rendered.
+    pub fn messages(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|(field, e)| format!("{field}: {e}"))
+            .collect()
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid search parameters. {}",
+            self.messages().join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// `latest`, a block number, `N:M` range or `N:latest` for the last N blocks.
+pub fn validate_blocks(value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if value == "latest" {
+        return Ok(());
+    }
+
+    let Some((start, end)) = value.split_once(':') else {
+        return parse_number(value, "block number").map(|_| ());
+    };
+
+    let max_block_range = max_block_range();
+    if end == "latest" {
+        let count = parse_number(start, "block count")?;
+        if count == 0 || count > max_block_range {
+            return Err(format!(
+                "must cover between 1 and {max_block_range} blocks, got {count}"
+            ));
+        }
+        return Ok(());
+    }
+
+    let start = parse_number(start, "range start")?;
+    let end = parse_number(end, "range end")?;
+    if start > end {
+        return Err(format!("range start {start} is after range end {end}"));
+    }
+    // Compared without `+ 1`, which overflows on a `0:u64::MAX` range
+    if end - start >= max_block_range {
+        return Err(format!(
+            "range covers {} blocks, the limit is {max_block_range}",
+            u128::from(end - start) + 1
+        ));
+    }
+    Ok(())
+}
+
+/// A transaction index or an `N:M` index range.
+pub fn validate_position(value: &str) -> Result<(), String> {
+    match value.trim().split_once(':') {
+        Some((start, end)) => {
+            let start = parse_number(start, "position start")?;
+            let end = parse_number(end, "position end")?;
+            if start > end {
+                return Err(format!(
+                    "position start {start} is after position end {end}"
+                ));
+            }
+            Ok(())
+        }
+        None => parse_number(value.trim(), "position").map(|_| ()),
+    }
+}
+
+pub fn validate_address_or_ens(value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if ADDRESS.is_match(value) || ENS_NAME.is_match(value) {
+        Ok(())
+    } else {
+        Err("must be a 0x-prefixed 20-byte hex address or an ENS name".to_string())
+    }
+}
+
+/// Like [`validate_address_or_ens`], also accepts `CREATE` for contract deployments.
+pub fn validate_to(value: &str) -> Result<(), String> {
+    if value.trim() == "CREATE" {
+        return Ok(());
+    }
+    validate_address_or_ens(value).map_err(|e| format!("{e}, or CREATE"))
+}
+
+/// An event signature, `/regexp/` or topic hash, optionally followed by
+/// `|<address>` to only match logs emitted by that contract.
+pub fn validate_event(value: &str) -> Result<(), String> {
+    let (matcher, contract) = match value.trim().split_once('|') {
+        Some((matcher, contract)) => (matcher, Some(contract)),
+        None => (value.trim(), None),
+    };
+
+    if !(SIGNATURE.is_match(matcher) || TOPIC.is_match(matcher)) {
+        validate_regexp(matcher).map_err(|_| {
+            "must be an event signature like Transfer(address,address,uint256), a topic hash or a /regexp/".to_string()
+        })?;
+    }
+
+    if let Some(contract) = contract
+        && !ADDRESS.is_match(contract)
+    {
+        return Err("contract after '|' must be a 0x-prefixed 20-byte hex address".to_string());
+    }
+    Ok(())
+}
+
+/// A method name, signature, 4-byte selector or `/regexp/`.
+pub fn validate_method(value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if IDENTIFIER.is_match(value) || SIGNATURE.is_match(value) || SELECTOR.is_match(value) {
+        return Ok(());
+    }
+    validate_regexp(value).map_err(|_| {
+        "must be a method name, signature like transfer(address,uint256), 4-byte selector or /regexp/".to_string()
+    })
+}
+
+/// A token address, optionally followed by `|<amount comparison>`.
+pub fn validate_erc20_transfer(value: &str) -> Result<(), String> {
+    let (token, amount) = match value.trim().split_once('|') {
+        Some((token, amount)) => (token, Some(amount)),
+        None => (value.trim(), None),
+    };
+
+    if !ADDRESS.is_match(token) {
+        return Err("token must be a 0x-prefixed 20-byte hex address".to_string());
+    }
+    if let Some(amount) = amount {
+        parse_comparison(amount).map_err(|e| format!("amount after '|' {e}"))?;
+    }
+    Ok(())
+}
+
+pub fn validate_comparison(value: &str) -> Result<(), String> {
+    parse_comparison(value).map(|_| ())
+}
+
+/// Parses `ge0.01ether`, `<=2gwei` etc. into the `ge`/`le` form mevlog expects.
+/// mevlog comparisons are inclusive, so strict `>`/`<` are rejected rather
+/// than quietly widened.
+pub fn parse_comparison(value: &str) -> Result<String, String> {
+    let Some(caps) = COMPARISON.captures(value.trim()) else {
+        return Err(format!(
+            "must be a comparison like ge0.01ether or le2gwei (units: {})",
+            AMOUNT_UNITS.join(", ")
+        ));
+    };
+
+    let operator = match &caps[1] {
+        "ge" | ">=" => "ge",
+        "le" | "<=" => "le",
+        strict => {
+            let inclusive = if strict == ">" { "ge" } else { "le" };
+            return Err(format!(
+                "'{strict}' is not supported, comparisons are inclusive, use {inclusive} instead"
+            ));
+        }
+    };
+    let unit = match caps[3].to_lowercase().as_str() {
+        "eth" => "ether".to_string(),
+        unit if unit.is_empty() || AMOUNT_UNITS.contains(&unit) => unit.to_string(),
+        unit => {
+            return Err(format!(
+                "unknown unit '{unit}', expected one of {}",
+                AMOUNT_UNITS.join(", ")
+            ));
+        }
+    };
+
+    Ok(format!("{operator}{}{unit}", &caps[2]))
+}
+
+/// Canonical form of a comparison, or the input unchanged if it doesn't parse.
+pub fn canonical_comparison(value: &str) -> String {
+    parse_comparison(value).unwrap_or_else(|_| value.to_string())
+}
+
+/// Like [`canonical_comparison`] for the amount part of an ERC20 transfer filter.
+pub fn canonical_erc20_transfer(value: &str) -> String {
+    match value.split_once('|') {
+        Some((token, amount)) => format!("{token}|{}", canonical_comparison(amount)),
+        None => value.to_string(),
+    }
+}
+
+fn validate_regexp(value: &str) -> Result<(), String> {
+    let pattern = value
+        .strip_prefix('/')
+        .and_then(|value| value.strip_suffix('/'))
+        .filter(|pattern| !pattern.is_empty())
+        .ok_or_else(|| "not a /regexp/".to_string())?;
+    Regex::new(pattern)
+        .map(|_| ())
+        .map_err(|e| format!("invalid regexp: {e}"))
+}
+
+fn parse_number(value: &str, name: &str) -> Result<u64, String> {
+    value
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| format!("{name} '{value}' is not a valid number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_grammar() {
+        for valid in ["latest", "22045570", "100:latest", "22045000:22045499"] {
+            assert!(validate_blocks(valid).is_ok(), "{valid}");
+        }
+
+        for invalid in [
+            "",
+            "abc",
+            "10:",
+            ":latest",
+            "0:latest",
+            "20:10",
+            "latest:10",
+        ] {
+            assert!(validate_blocks(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn blocks_range_limit() {
+        assert!(validate_blocks("500:latest").is_ok());
+        assert!(validate_blocks("501:latest").is_err());
+        assert!(validate_blocks("1000:1499").is_ok());
+        assert!(validate_blocks("1000:1500").is_err());
+    }
+
+    #[test]
+    fn blocks_range_limit_does_not_overflow() {
+        let error = validate_blocks(&format!("0:{}", u64::MAX)).unwrap_err();
+        assert!(error.contains("range covers 18446744073709551616 blocks"));
+    }
+
+    #[test]
+    fn position_grammar() {
+        assert!(validate_position("0").is_ok());
+        assert!(validate_position("0:5").is_ok());
+        assert!(validate_position("5:0").is_err());
+        assert!(validate_position("first").is_err());
+    }
+
+    #[test]
+    fn addresses_and_ens_names() {
+        assert!(validate_address_or_ens("0xbaa3ef11659d347aae75c7bb67e29f1f8bb90843").is_ok());
+        assert!(validate_address_or_ens("jaredfromsubway.eth").is_ok());
+        assert!(validate_address_or_ens("sub.vitalik.eth").is_ok());
+        assert!(validate_address_or_ens("0xbaa3ef11659d347aae75c7bb67e29f1f8bb9084").is_err());
+        assert!(validate_address_or_ens("vitalik").is_err());
+        assert!(validate_address_or_ens("vitalik.com").is_err());
+        assert!(validate_to("CREATE").is_ok());
+        assert!(validate_address_or_ens("CREATE").is_err());
+    }
+
+    #[test]
+    fn events() {
+        assert!(validate_event("Transfer(address,address,uint256)").is_ok());
+        assert!(
+            validate_event(
+                "Transfer(address,address,uint256)|0x6982508145454ce325ddbe47a25d4ec3d2311933"
+            )
+            .is_ok()
+        );
+        assert!(validate_event("/(Swap).+/").is_ok());
+        assert!(
+            validate_event("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")
+                .is_ok()
+        );
+        assert!(validate_event("Transfer(address").is_err());
+        assert!(validate_event("/(Swap/").is_err());
+        assert!(validate_event("Transfer(address)|0x1234").is_err());
+    }
+
+    #[test]
+    fn methods() {
+        for valid in [
+            "transfer",
+            "transfer(address,uint256)",
+            "0xa9059cbb",
+            "/swap.*/",
+        ] {
+            assert!(validate_method(valid).is_ok(), "{valid}");
+        }
+        assert!(validate_method("transfer address").is_err());
+        assert!(validate_method("0xa9059c").is_err());
+    }
+
+    #[test]
+    fn erc20_transfers() {
+        assert!(validate_erc20_transfer("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").is_ok());
+        assert!(
+            validate_erc20_transfer("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48|ge100gwei").is_ok()
+        );
+        assert!(validate_erc20_transfer("usdc").is_err());
+        assert!(
+            validate_erc20_transfer("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48|lots").is_err()
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(parse_comparison("ge0.01ether").unwrap(), "ge0.01ether");
+        assert_eq!(parse_comparison(">=0.01 eth").unwrap(), "ge0.01ether");
+        assert_eq!(parse_comparison("<=2gwei").unwrap(), "le2gwei");
+        assert_eq!(parse_comparison("le1000").unwrap(), "le1000");
+        assert!(parse_comparison("0.01ether").is_err());
+        assert!(parse_comparison("ge0.01btc").is_err());
+        assert!(parse_comparison("=1gwei").is_err());
+        assert!(
+            parse_comparison(">0.01ether")
+                .unwrap_err()
+                .contains("use ge")
+        );
+        assert!(parse_comparison("<2gwei").unwrap_err().contains("use le"));
+        assert_eq!(
+            canonical_erc20_transfer("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48|>=100gwei"),
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48|ge100gwei"
+        );
+    }
+
+    #[test]
+    fn errors_are_keyed_by_field() {
+        let mut errors = ValidationErrors::default();
+        errors.check("blocks", validate_blocks("abc"));
+        errors.check("tx_cost", validate_comparison("ge0.01ether"));
+
+        let errors = errors.into_result().unwrap_err();
+        assert!(errors.get("blocks").is_some());
+        assert!(errors.get("tx_cost").is_none());
+        assert!(errors.to_json()["errors"]["blocks"].is_string());
+    }
+}